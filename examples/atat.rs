@@ -1,68 +1,14 @@
-use std::io::{ErrorKind, Read, Write};
+use std::io::{ErrorKind, Read};
 use std::thread;
 use std::time::Duration;
 use std::{env, str};
 
 use serialport::{self, SerialPortSettings};
 
-use nb;
-
-use embedded_hal as hal;
-
 use atat::ATATInterface;
 
 use esp01::atat as eatat;
-
-pub struct Serial<T: Read + Write>(pub T);
-
-impl<T: Read + Write> hal::serial::Read<u8> for Serial<T> {
-    type Error = ErrorKind;
-
-    fn read(&mut self) -> nb::Result<u8, Self::Error> {
-        let mut buffer = [0; 1];
-        let bytes_read = self.0.read(&mut buffer).map_err(translate_io_errors)?;
-        if bytes_read == 1 {
-            Ok(buffer[0])
-        } else {
-            Err(nb::Error::WouldBlock)
-        }
-    }
-}
-
-/// Helper to convert std::io::Error to the nb::Error
-fn translate_io_errors(err: std::io::Error) -> nb::Error<ErrorKind> {
-    match err.kind() {
-        ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted => {
-            nb::Error::WouldBlock
-        }
-        err => nb::Error::Other(err),
-    }
-}
-
-impl<T: Read + Write> hal::serial::Write<u8> for Serial<T> {
-    type Error = ErrorKind;
-
-    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
-        self.0.write(&[word]).map_err(translate_io_errors)?;
-        Ok(())
-    }
-
-    fn flush(&mut self) -> nb::Result<(), Self::Error> {
-        self.0.flush().map_err(translate_io_errors)
-    }
-}
-
-impl<T: Read + Write> core::fmt::Write for Serial<T> {
-    fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        use embedded_hal::serial::Write;
-        let _ = s
-            .as_bytes()
-            .iter()
-            .map(|c| nb::block!(self.write(*c)))
-            .last();
-        Ok(())
-    }
-}
+use esp01::host::Serial;
 
 fn main() {
     env_logger::init();
@@ -1,72 +1,19 @@
-use std::io::{ErrorKind, Read, Write};
+use std::net::Ipv4Addr;
 use std::time::Duration;
 use std::{env, str};
 
 use serial::{self, core::SerialPort};
 
-use nb;
-
-use embedded_hal as hal;
-
 use esp01::errors::EResult;
-use esp01::esp01;
+use esp01::esp01_with_timer;
+use esp01::host::{Serial, SysTimer};
 use esp01::ConnectionMode::*;
-use esp01::Mode::*;
+use esp01::ConnectOptions;
+use esp01::Station;
 use esp01::Persist::*;
 use esp01::QueryMode::*;
 
-pub struct Serial<T: Read + Write>(pub T);
-
-impl<T: Read + Write> hal::serial::Read<u8> for Serial<T> {
-    type Error = ErrorKind;
-
-    fn read(&mut self) -> nb::Result<u8, Self::Error> {
-        let mut buffer = [0; 1];
-        let bytes_read = self.0.read(&mut buffer).map_err(translate_io_errors)?;
-        if bytes_read == 1 {
-            Ok(buffer[0])
-        } else {
-            Err(nb::Error::WouldBlock)
-        }
-    }
-}
-
-/// Helper to convert std::io::Error to the nb::Error
-fn translate_io_errors(err: std::io::Error) -> nb::Error<ErrorKind> {
-    match err.kind() {
-        ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted => {
-            nb::Error::WouldBlock
-        }
-        err => nb::Error::Other(err),
-    }
-}
-
-impl<T: Read + Write> hal::serial::Write<u8> for Serial<T> {
-    type Error = ErrorKind;
-
-    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
-        self.0.write(&[word]).map_err(translate_io_errors)?;
-        Ok(())
-    }
-
-    fn flush(&mut self) -> nb::Result<(), Self::Error> {
-        self.0.flush().map_err(translate_io_errors)
-    }
-}
-
-impl<T: Read + Write> core::fmt::Write for Serial<T> {
-    fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        use embedded_hal::serial::Write;
-        let _ = s
-            .as_bytes()
-            .iter()
-            .map(|c| nb::block!(self.write(*c)))
-            .last();
-        Ok(())
-    }
-}
-
-fn main() -> EResult<()> {
+fn main() -> EResult<(), std::io::Error> {
     let args: Vec<String> = env::args().collect();
     if args.len() != 2 {
         println!("Usage: {} <path-to-serial>", args[0]);
@@ -90,12 +37,12 @@ fn main() -> EResult<()> {
         .expect("Could not set serial port timeout");
 
     let s = Serial(port);
-    let mut esp01 = esp01(s);
+    let mut esp01 = esp01_with_timer(s, SysTimer::new());
     let r = esp01.get_version()?;
     println!("{:?}", r);
     println!("{}", str::from_utf8(r).unwrap());
 
-    let esp01 = esp01.set_mode(StationMode, DontSave)?;
+    let esp01 = esp01.set_mode(Station, DontSave)?;
     //println!("{}", str::from_utf8(r).unwrap());
 
     let mut esp01 = esp01.connect_ap("uap_revived", "uberandpipsnetwork", DontSave)?;
@@ -106,7 +53,7 @@ fn main() -> EResult<()> {
     let r = esp01.get_station_mac(Current)?;
     println!("{}", str::from_utf8(r).unwrap());
 
-    let r = esp01.connect(TCP, "10.0.0.4", "8000")?;
+    let r = esp01.connect(&ConnectOptions::new(TCP, Ipv4Addr::new(10, 0, 0, 4), 8000))?;
 
     Ok(())
 
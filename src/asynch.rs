@@ -0,0 +1,712 @@
+//! Async driver for executors like Embassy
+//! The serial port is split into a `Client` that sends commands and an `Ingress` that has
+//! to run in its own task. The ingress reads everything the module sends and hands responses,
+//! notifications and `+IPD` data over to the client through a shared `State`.
+//! The client does not time out by itself, calls can be wrapped in a timeout like
+//! `embassy_time::with_timeout`.
+//! Serial errors are reported as their `embedded_io::ErrorKind`: a read error reaches the
+//! client through the `State`, which is created before the serial port is split and can not
+//! name the error types of its halves, and the halves usually have different error types.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embedded_io_async::{Error as _, Read, Write};
+use heapless::consts;
+use heapless::spsc::Queue;
+
+use crate::buffer::RingBuffer;
+use crate::errors::{EResult, Error, ErrorCode, JoinError};
+use crate::urc::Urc;
+use crate::{
+    is_echo, parse, ConnectCommand, ConnectOptions, ConnectionMode, Decimal, LinkId, Mode, Persist,
+    SslAuth, AT, CR, ERROR, FAIL, IPD, LF, LINE_END, MAX_LINKS, MAX_SEND_LEN, OK, PROMPT,
+    SEND_FAIL, SEND_OK, SSL_BUFFER_SIZE,
+};
+
+/// State shared by the client and the ingress
+pub struct State<M: RawMutex, const N: usize = 512, const RX: usize = 256> {
+    shared: Mutex<M, RefCell<Shared<N, RX>>>,
+    response: Signal<M, EResult<()>>,
+    prompt: Signal<M, ()>,
+    data: Signal<M, ()>,
+    urc: Signal<M, ()>,
+}
+
+struct Shared<const N: usize, const RX: usize> {
+    response: [u8; N],
+    len: usize,
+    overflowed: bool,
+    rx: [RingBuffer<RX>; MAX_LINKS],
+    links: u8,
+    ssl_buffer_size: Option<u16>,
+    urcs: Queue<Urc, consts::U8>,
+}
+
+impl<M: RawMutex, const N: usize, const RX: usize> State<M, N, RX> {
+    pub fn new() -> Self {
+        State {
+            shared: Mutex::new(RefCell::new(Shared {
+                response: [0; N],
+                len: 0,
+                overflowed: false,
+                rx: core::array::from_fn(|_| RingBuffer::new()),
+                links: 0,
+                ssl_buffer_size: None,
+                urcs: Queue::new(),
+            })),
+            response: Signal::new(),
+            prompt: Signal::new(),
+            data: Signal::new(),
+            urc: Signal::new(),
+        }
+    }
+
+    fn with<U>(&self, f: impl FnOnce(&mut Shared<N, RX>) -> U) -> U {
+        self.shared.lock(|shared| f(&mut shared.borrow_mut()))
+    }
+}
+
+impl<M: RawMutex, const N: usize, const RX: usize> Default for State<M, N, RX> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Creates the client and the ingress for the two halves of a serial port
+pub fn new<R, W, M, const N: usize, const RX: usize>(
+    reader: R,
+    writer: W,
+    state: &State<M, N, RX>,
+) -> (Client<'_, W, M, N, RX>, Ingress<'_, R, M, N, RX>)
+where
+    R: Read,
+    W: Write,
+    M: RawMutex,
+{
+    let client = Client {
+        serial: writer,
+        state,
+        buf: [0; N],
+        len: 0,
+    };
+    let ingress = Ingress {
+        serial: reader,
+        state,
+        line: [0; N],
+        len: 0,
+        frame: Frame::Line,
+        code: None,
+    };
+
+    (client, ingress)
+}
+
+/// What the ingress is in the middle of reading
+#[derive(Copy, Clone)]
+enum Frame {
+    Line,
+    /// The `> ` prompt, the space still has to be skipped
+    Prompt,
+    /// The header of an `+IPD` frame
+    Header,
+    /// The data of an `+IPD` frame
+    Data {
+        link: usize,
+        remaining: usize,
+    },
+}
+
+/// Reads from the module and dispatches what it sent, see `run`
+pub struct Ingress<'a, R, M: RawMutex, const N: usize, const RX: usize> {
+    serial: R,
+    state: &'a State<M, N, RX>,
+    line: [u8; N],
+    len: usize,
+    frame: Frame,
+    /// The reason for an `ERROR` the firmware reported
+    code: Option<ErrorCode>,
+}
+
+impl<'a, R, M, const N: usize, const RX: usize> Ingress<'a, R, M, N, RX>
+where
+    R: Read,
+    M: RawMutex,
+{
+    /// Reads from the serial port forever, has to run in its own task
+    pub async fn run(&mut self) -> ! {
+        let mut buf = [0; 64];
+        loop {
+            match self.serial.read(&mut buf).await {
+                Ok(n) => self.ingest(&buf[0..n]),
+                // the state only carries the kind, see the module documentation
+                Err(err) => self
+                    .state
+                    .response
+                    .signal(Err(Error::SerialRead(err.kind()))),
+            }
+        }
+    }
+
+    /// Processes bytes received from the module
+    pub fn ingest(&mut self, mut bytes: &[u8]) {
+        while let Some(&byte) = bytes.first() {
+            match self.frame {
+                Frame::Data { link, remaining } => {
+                    let n = remaining.min(bytes.len());
+                    self.state.with(|shared| {
+                        for b in &bytes[0..n] {
+                            shared.rx[link].push(*b);
+                        }
+                    });
+                    log_rx_data!(link, n);
+                    self.state.data.signal(());
+
+                    self.frame = match remaining - n {
+                        0 => Frame::Line,
+                        remaining => Frame::Data { link, remaining },
+                    };
+                    bytes = &bytes[n..];
+                    continue;
+                }
+                Frame::Header => match byte {
+                    b':' => {
+                        self.frame = match parse::ipd_header(&self.line[0..self.len]) {
+                            Some((link, remaining, _)) if remaining > 0 => {
+                                Frame::Data { link, remaining }
+                            }
+                            _ => Frame::Line,
+                        };
+                        self.len = 0;
+                    }
+                    _ if self.len < self.line.len() => {
+                        self.line[self.len] = byte;
+                        self.len += 1;
+                    }
+                    _ => {
+                        self.frame = Frame::Line;
+                        self.len = 0;
+                    }
+                },
+                Frame::Prompt => {
+                    self.frame = Frame::Line;
+                    if byte != b' ' {
+                        self.line_byte(byte);
+                    }
+                }
+                Frame::Line => self.line_byte(byte),
+            }
+            bytes = &bytes[1..];
+        }
+    }
+
+    fn line_byte(&mut self, byte: u8) {
+        match byte {
+            LF => {
+                self.handle_line();
+                self.len = 0;
+            }
+            CR => {}
+            PROMPT if self.len == 0 => {
+                self.frame = Frame::Prompt;
+                self.state.prompt.signal(());
+            }
+            _ if self.len < self.line.len() => {
+                self.line[self.len] = byte;
+                self.len += 1;
+
+                if self.line[0..self.len] == *IPD {
+                    self.frame = Frame::Header;
+                    self.len = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Passes a complete line on to the client
+    fn handle_line(&mut self) {
+        let line = &self.line[0..self.len];
+        log_rx!(line);
+
+        // the echo of the command is not part of the response
+        if line.is_empty() || is_echo(line) {
+            return;
+        }
+
+        if let Some(urc) = Urc::parse(line) {
+            self.state.with(|shared| {
+                match urc {
+                    Urc::LinkConnected(link) => shared.links |= link.mask(),
+                    Urc::LinkClosed(link) => shared.links &= !link.mask(),
+                    // the module restarted, its links and the SSL buffer size are gone
+                    Urc::Ready => {
+                        shared.links = 0;
+                        shared.ssl_buffer_size = None;
+                    }
+                    _ => {}
+                }
+
+                // the oldest notification is dropped if nobody polls them
+                if let Err(urc) = shared.urcs.enqueue(urc) {
+                    shared.urcs.dequeue();
+                    let _ = shared.urcs.enqueue(urc);
+                }
+            });
+            match urc {
+                // wakes up a receive that waits on the link
+                Urc::LinkClosed(_) | Urc::Ready => self.state.data.signal(()),
+                // the module dropped the command
+                Urc::Busy => self.state.response.signal(Err(Error::Busy)),
+                _ => {}
+            }
+            self.state.urc.signal(());
+            return;
+        }
+
+        if let Some(error) = ErrorCode::parse(line) {
+            self.code = Some(error);
+            return;
+        }
+
+        let result = match line {
+            OK | SEND_OK => Ok(()),
+            ERROR => Err(Error::CommandError(self.code)),
+            FAIL => Err(Error::CommandFailed),
+            SEND_FAIL => Err(Error::SendFailed),
+            _ => {
+                self.state.with(|shared| {
+                    let start = if shared.len == 0 {
+                        0
+                    } else {
+                        shared.len + LINE_END.len()
+                    };
+                    let end = start + line.len();
+                    if shared.overflowed || end > N {
+                        shared.overflowed = true;
+                        return;
+                    }
+
+                    if start > 0 {
+                        shared.response[shared.len..start].copy_from_slice(&LINE_END);
+                    }
+                    shared.response[start..end].copy_from_slice(line);
+                    shared.len = end;
+                });
+                return;
+            }
+        };
+
+        self.code = None;
+        self.state.response.signal(result);
+    }
+}
+
+/// Sends commands to the module, the async counterpart of `Esp01`
+/// Links are used in multi-connection mode, see `enable_multiple_connections`.
+pub struct Client<'a, W, M: RawMutex, const N: usize, const RX: usize> {
+    serial: W,
+    state: &'a State<M, N, RX>,
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<'a, W, M, const N: usize, const RX: usize> Client<'a, W, M, N, RX>
+where
+    W: Write,
+    M: RawMutex,
+{
+    /// Drops what is left of the previous response before a command is written
+    fn reset_response(&mut self) {
+        self.state.response.reset();
+        self.state.prompt.reset();
+        self.state.with(|shared| {
+            shared.len = 0;
+            shared.overflowed = false;
+        });
+    }
+
+    /// Writes a command or query to the module
+    async fn write_command(&mut self, command: &[&str], query: bool) -> EResult<()> {
+        self.reset_response();
+
+        self.write(&AT).await?;
+        for part in command {
+            self.write(part.as_bytes()).await?;
+        }
+        if query {
+            self.write(b"?").await?;
+        }
+        self.write(&LINE_END).await?;
+        log_tx!(command, query);
+
+        Ok(())
+    }
+
+    /// Reports write errors as their kind like read errors, see the module documentation
+    async fn write(&mut self, data: &[u8]) -> EResult<()> {
+        self.serial
+            .write_all(data)
+            .await
+            .map_err(|err| Error::SerialWrite(err.kind()))
+    }
+
+    /// Waits for the end of the response and copies its lines into `buf`,
+    /// also if the command failed
+    async fn wait_response(&mut self) -> EResult<()> {
+        let result = self.state.response.wait().await;
+        let overflowed = self.state.with(|shared| {
+            self.buf[0..shared.len].copy_from_slice(&shared.response[0..shared.len]);
+            self.len = shared.len;
+            shared.len = 0;
+
+            core::mem::replace(&mut shared.overflowed, false)
+        });
+
+        match result {
+            Ok(()) if overflowed => Err(Error::ResponseOverflow),
+            result => result,
+        }
+    }
+
+    /// Sends a command and reads its response
+    async fn send_command(&mut self, command: &[&str]) -> EResult<&[u8]> {
+        self.write_command(command, false).await?;
+        self.wait_response().await?;
+
+        Ok(&self.buf[0..self.len])
+    }
+
+    /// Gets ESP01 version information
+    pub async fn get_version(&mut self) -> EResult<&[u8]> {
+        self.send_command(&["GMR"]).await
+    }
+
+    /// Enables or disables the echo of commands (`ATE1`/`ATE0`)
+    /// The ingress does not need the echo, without it the module sends about half as much.
+    pub async fn set_echo(&mut self, enable: bool) -> EResult<()> {
+        let command = if enable { "E1" } else { "E0" };
+        self.reset_response();
+
+        self.write(b"AT").await?;
+        self.write(command.as_bytes()).await?;
+        self.write(&LINE_END).await?;
+        log_tx_basic!(command);
+
+        self.wait_response().await
+    }
+
+    /// Sets the Wi-Fi mode
+    pub async fn set_mode(&mut self, mode: Mode, persist: Persist) -> EResult<()> {
+        self.send_command(&["CWMODE", persist.as_str(), mode.as_str()])
+            .await?;
+
+        Ok(())
+    }
+
+    /// Connects to an access point
+    /// Fails with `Error::JoinFailed` if the module reports why it could not connect.
+    pub async fn connect_ap(
+        &mut self,
+        ssid: &str,
+        password: &str,
+        persist: Persist,
+    ) -> EResult<()> {
+        self.write_command(
+            &[
+                "CWJAP",
+                persist.as_str(),
+                "\"",
+                ssid,
+                "\",\"",
+                password,
+                "\"",
+            ],
+            false,
+        )
+        .await?;
+
+        // older firmware ends a failed join with `FAIL`, ESP-AT 2.x with `ERROR`
+        match self.wait_response().await {
+            Err(err @ Error::CommandFailed) | Err(err @ Error::CommandError(_)) => {
+                let reason = self.buf[0..self.len]
+                    .split(|b| *b == LF)
+                    .map(|line| line.strip_suffix(&[CR]).unwrap_or(line))
+                    .find_map(JoinError::parse);
+                match reason {
+                    Some(reason) => Err(Error::JoinFailed(reason)),
+                    None => Err(err),
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Enables multi-connection mode
+    pub async fn enable_multiple_connections(&mut self) -> EResult<()> {
+        self.send_command(&["CIPMUX=1"]).await?;
+
+        Ok(())
+    }
+
+    /// Sets the size of the SSL buffer in bytes (2048-4096)
+    /// The default buffer is too small for the handshake with most servers,
+    /// so SSL connections can only be opened once this was set.
+    pub async fn set_ssl_buffer_size(&mut self, size: u16) -> EResult<()> {
+        if !SSL_BUFFER_SIZE.contains(&size) {
+            return Err(Error::InvalidSslBufferSize);
+        }
+
+        let param = Decimal::new(size as usize);
+        self.send_command(&["CIPSSLSIZE=", param.as_str()]).await?;
+        self.state
+            .with(|shared| shared.ssl_buffer_size = Some(size));
+
+        Ok(())
+    }
+
+    /// Sets which certificates SSL connections check
+    pub async fn set_ssl_auth(&mut self, auth: SslAuth) -> EResult<()> {
+        self.send_command(&["CIPSSLCCONF=", auth.as_str()]).await?;
+
+        Ok(())
+    }
+
+    /// Connects to an endpoint on the first free link
+    /// An SSL connection fails with `Error::SslNotConfigured` before anything is sent
+    /// if the SSL buffer was not enlarged with `set_ssl_buffer_size`.
+    pub async fn connect(&mut self, options: &ConnectOptions<'_>) -> EResult<LinkId> {
+        let ssl_buffer_size = self.state.with(|shared| shared.ssl_buffer_size);
+        if options.connection_mode == ConnectionMode::SSL && ssl_buffer_size.is_none() {
+            return Err(Error::SslNotConfigured);
+        }
+
+        let link = self.state.with(|shared| {
+            let link = (0..MAX_LINKS as u8)
+                .filter_map(LinkId::new)
+                .find(|link| shared.links & link.mask() == 0)?;
+            shared.rx[link.0 as usize].clear();
+
+            Some(link)
+        });
+        let link = link.ok_or(Error::NoFreeLink)?;
+
+        let command = ConnectCommand::new(Some(link), options);
+        if let Some(sni) = command.sni_parts() {
+            self.send_command(&sni).await?;
+        }
+        self.send_command(&command.parts()).await?;
+        self.state.with(|shared| shared.links |= link.mask());
+
+        Ok(link)
+    }
+
+    /// Returns whether the link is open
+    pub fn is_connected(&self, link: LinkId) -> bool {
+        self.state.with(|shared| shared.links & link.mask() != 0)
+    }
+
+    /// Sends data over the link
+    /// At most `MAX_SEND_LEN` bytes can be sent at once.
+    pub async fn send(&mut self, link: LinkId, data: &[u8]) -> EResult<()> {
+        if data.len() > MAX_SEND_LEN {
+            return Err(Error::SendTooLarge);
+        }
+
+        let len = Decimal::new(data.len());
+        self.send_command(&["CIPSEND=", link.as_str(), ",", len.as_str()])
+            .await?;
+
+        self.state.prompt.wait().await;
+        self.write(data).await?;
+        log_tx_data!(data);
+
+        self.wait_response().await
+    }
+
+    /// Reads data received over the link into `buf` and returns the number of bytes read
+    /// Waits until data arrives, returns `Ok(0)` once the link is closed.
+    /// `Error::ReceiveOverflow` is returned once if data was dropped because the receive
+    /// buffer was full.
+    pub async fn receive(&mut self, link: LinkId, buf: &mut [u8]) -> EResult<usize> {
+        loop {
+            let received = self.state.with(|shared| {
+                let rx = &mut shared.rx[link.0 as usize];
+                if rx.take_overflow() {
+                    return Some(Err(Error::ReceiveOverflow));
+                }
+
+                match rx.read(buf) {
+                    0 if shared.links & link.mask() != 0 => None,
+                    n => Some(Ok(n)),
+                }
+            });
+            if let Some(received) = received {
+                return received;
+            }
+
+            self.state.data.wait().await;
+        }
+    }
+
+    /// Closes the link
+    pub async fn close(&mut self, link: LinkId) -> EResult<()> {
+        self.send_command(&["CIPCLOSE=", link.as_str()]).await?;
+        self.state.with(|shared| shared.links &= !link.mask());
+
+        Ok(())
+    }
+
+    /// Returns the next notification the module sent without being asked, if there is one
+    pub fn poll_urc(&mut self) -> Option<Urc> {
+        self.state.with(|shared| shared.urcs.dequeue())
+    }
+
+    /// Waits for the next notification the module sends without being asked
+    pub async fn next_urc(&mut self) -> Urc {
+        loop {
+            if let Some(urc) = self.poll_urc() {
+                return urc;
+            }
+
+            self.state.urc.wait().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use embassy_futures::block_on;
+    use embassy_futures::join::join;
+    use embassy_futures::yield_now;
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use embedded_io_async::ErrorType;
+
+    use super::*;
+
+    /// Collects what the client writes, the ingress is fed by the tests
+    #[derive(Default)]
+    struct Port {
+        output: Vec<u8>,
+    }
+
+    impl ErrorType for Port {
+        type Error = core::convert::Infallible;
+    }
+
+    impl Read for Port {
+        async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+            core::future::pending().await
+        }
+    }
+
+    impl Write for Port {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn join_failures() {
+        let state: State<NoopRawMutex> = State::new();
+        let (mut client, mut ingress) = new(Port::default(), Port::default(), &state);
+
+        let (result, ()) = block_on(join(
+            client.connect_ap("ap", "wrong", Persist::DontSave),
+            async { ingress.ingest(b"+CWJAP:2\r\n\r\nFAIL\r\n") },
+        ));
+        assert_eq!(result, Err(Error::JoinFailed(JoinError::WrongPassword)));
+
+        let (result, ()) = block_on(join(
+            client.connect_ap("ap", "secret", Persist::DontSave),
+            async {
+                ingress.ingest(b"AT+CWJAP_CUR=\"ap\",\"secret\"\r\r\n+CWJAP:3\r\n\r\nERROR\r\n")
+            },
+        ));
+        assert_eq!(result, Err(Error::JoinFailed(JoinError::APNotFound)));
+
+        let (result, ()) = block_on(join(
+            client.connect_ap("ap", "secret", Persist::DontSave),
+            async { ingress.ingest(b"\r\nERROR\r\n") },
+        ));
+        assert_eq!(result, Err(Error::CommandError(None)));
+        assert!(client
+            .serial
+            .output
+            .ends_with(b"AT+CWJAP_CUR=\"ap\",\"secret\"\r\n"));
+    }
+
+    #[test]
+    fn ssl_buffer_size() {
+        let state: State<NoopRawMutex> = State::new();
+        let (mut client, mut ingress) = new(Port::default(), Port::default(), &state);
+        let options = ConnectOptions::new(ConnectionMode::SSL, "1.2.3.4", 443);
+
+        assert_eq!(
+            block_on(client.set_ssl_buffer_size(8192)),
+            Err(Error::InvalidSslBufferSize)
+        );
+        assert!(client.serial.output.is_empty());
+
+        let (result, ()) = block_on(join(client.set_ssl_buffer_size(2048), async {
+            ingress.ingest(b"\r\nOK\r\n")
+        }));
+        assert_eq!(result, Ok(()));
+
+        // the module restarted and forgot the size
+        ingress.ingest(b"ready\r\n");
+        assert_eq!(
+            block_on(client.connect(&options)),
+            Err(Error::SslNotConfigured)
+        );
+    }
+
+    #[test]
+    fn ingest() {
+        let state: State<NoopRawMutex> = State::new();
+        let (mut client, mut ingress) = new(Port::default(), Port::default(), &state);
+        let link = LinkId(0);
+
+        // a response that arrives in pieces, with data and a notification in between
+        let (version, ()) = block_on(join(
+            async { client.get_version().await.map(|version| version.to_vec()) },
+            async {
+                let chunks: [&[u8]; 6] = [
+                    b"AT+GMR\r\r\nAT vers",
+                    b"ion:1.7\r\n+IP",
+                    b"D,0,5:ab",
+                    b"c\r\n0,CONNECT\r",
+                    b"\n\r\nO",
+                    b"K\r\n",
+                ];
+                for chunk in chunks.iter() {
+                    ingress.ingest(chunk);
+                }
+            },
+        ));
+        assert_eq!(version.as_deref(), Ok(&b"AT version:1.7"[..]));
+        assert_eq!(client.poll_urc(), Some(Urc::LinkConnected(link)));
+        assert!(client.is_connected(link));
+        let mut buf = [0; 8];
+        assert_eq!(block_on(client.receive(link, &mut buf)), Ok(5));
+        assert_eq!(&buf[0..5], b"abc\r\n");
+
+        // the payload is written after the prompt
+        let (result, ()) = block_on(join(client.send(link, b"hi"), async {
+            ingress.ingest(b"AT+CIPSEND=0,2\r\r\n\r\nOK\r\n> ");
+            yield_now().await;
+            ingress.ingest(b"\r\nRecv 2 bytes\r\n\r\nSEND OK\r\n");
+        }));
+        assert_eq!(result, Ok(()));
+        assert!(client.serial.output.ends_with(b"AT+CIPSEND=0,2\r\nhi"));
+
+        ingress.ingest(b"0,CLOSED\r\n");
+        assert!(!client.is_connected(link));
+        assert_eq!(block_on(client.receive(link, &mut buf)), Ok(0));
+    }
+}
@@ -46,12 +46,7 @@ impl ATATCmd for At {
     }
 
     fn parse(&self, resp: &str) -> Result<Self::Response, atat::Error> {
-        println!("Parsing: {}", resp);
-        let e = serde_at::from_str::<CWMODE_CUR>(resp);
-        println!("{:?}", e);
-
-        e.map_err(|e| atat::Error::InvalidResponse)
-        //Ok(EmptyResponse)
+        serde_at::from_str::<CWMODE_CUR>(resp).map_err(|_| atat::Error::InvalidResponse)
     }
 }
 
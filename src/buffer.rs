@@ -0,0 +1,130 @@
+/// A bounded ring buffer for data received from the module
+pub(crate) struct RingBuffer<const N: usize> {
+    buf: [u8; N],
+    head: usize,
+    len: usize,
+    overflowed: bool,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    pub fn new() -> Self {
+        RingBuffer {
+            buf: [0; N],
+            head: 0,
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Appends a byte, dropping it and remembering the overflow if the buffer is full
+    pub fn push(&mut self, byte: u8) {
+        if self.len == N {
+            self.overflowed = true;
+            return;
+        }
+
+        let tail = (self.head + self.len) % N;
+        self.buf[tail] = byte;
+        self.len += 1;
+    }
+
+    /// Moves as many buffered bytes as fit into `out` and returns their count
+    pub fn read(&mut self, out: &mut [u8]) -> usize {
+        let n = out.len().min(self.len);
+        for b in out.iter_mut().take(n) {
+            *b = self.buf[self.head];
+            self.head = (self.head + 1) % N;
+        }
+        self.len -= n;
+
+        n
+    }
+
+    /// Returns how many bytes still fit
+    pub fn space(&self) -> usize {
+        N - self.len
+    }
+
+    /// Drops up to `n` buffered bytes
+    pub fn discard(&mut self, n: usize) {
+        let n = n.min(self.len);
+        self.head = (self.head + n) % N;
+        self.len -= n;
+    }
+
+    /// Remembers that data was dropped before it reached the buffer
+    pub fn set_overflow(&mut self) {
+        self.overflowed = true;
+    }
+
+    /// Returns whether no data is buffered
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Drops all buffered data
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.overflowed = false;
+    }
+
+    /// Returns whether data was dropped since the last call
+    pub fn take_overflow(&mut self) -> bool {
+        let overflowed = self.overflowed;
+        self.overflowed = false;
+
+        overflowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_around() {
+        let mut rx: RingBuffer<4> = RingBuffer::new();
+        let mut out = [0; 4];
+        for b in b"abc" {
+            rx.push(*b);
+        }
+        assert_eq!(rx.read(&mut out[0..2]), 2);
+        for b in b"def" {
+            rx.push(*b);
+        }
+        assert_eq!(rx.space(), 0);
+        assert_eq!(rx.read(&mut out), 4);
+        assert_eq!(&out, b"cdef");
+        assert!(rx.is_empty());
+        assert!(!rx.take_overflow());
+    }
+
+    #[test]
+    fn overflow() {
+        let mut rx: RingBuffer<2> = RingBuffer::new();
+        let mut out = [0; 4];
+        for b in b"xyz" {
+            rx.push(*b);
+        }
+        assert!(rx.take_overflow());
+        assert!(!rx.take_overflow());
+        assert_eq!(rx.read(&mut out), 2);
+        assert_eq!(&out[0..2], b"xy");
+    }
+
+    #[test]
+    fn discard() {
+        let mut rx: RingBuffer<4> = RingBuffer::new();
+        let mut out = [0; 4];
+        for b in b"abcd" {
+            rx.push(*b);
+        }
+        rx.discard(3);
+        assert_eq!(rx.space(), 3);
+        rx.discard(5);
+        assert!(rx.is_empty());
+        rx.push(b'e');
+        assert_eq!(rx.read(&mut out), 1);
+        assert_eq!(out[0], b'e');
+    }
+}
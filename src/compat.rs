@@ -0,0 +1,206 @@
+//! Adapter for serial ports that only implement the `embedded-hal` 0.2 serial traits
+
+use core::fmt::Debug;
+
+use embedded_hal::serial;
+use embedded_io::{ErrorKind, ErrorType, Read, ReadReady, Write};
+
+/// Makes an `embedded-hal` 0.2 serial port usable by the driver
+pub struct Compat<S> {
+    serial: S,
+    /// A byte that was read to find out whether the port is ready
+    peeked: Option<u8>,
+}
+
+impl<S> Compat<S> {
+    pub fn new(serial: S) -> Self {
+        Compat {
+            serial,
+            peeked: None,
+        }
+    }
+
+    /// Returns the serial port, a byte that was already read from the port is lost
+    pub fn into_inner(self) -> S {
+        self.serial
+    }
+}
+
+/// An error of the wrapped serial port
+#[derive(Debug, Eq, PartialEq)]
+pub struct CompatError<E>(pub E);
+
+impl<E: Debug> embedded_io::Error for CompatError<E> {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl<S, E> ErrorType for Compat<S>
+where
+    S: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+    E: Debug,
+{
+    type Error = CompatError<E>;
+}
+
+impl<S, E> ReadReady for Compat<S>
+where
+    S: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+    E: Debug,
+{
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        if self.peeked.is_some() {
+            return Ok(true);
+        }
+
+        match self.serial.read() {
+            Ok(byte) => {
+                self.peeked = Some(byte);
+                Ok(true)
+            }
+            Err(nb::Error::WouldBlock) => Ok(false),
+            Err(nb::Error::Other(err)) => Err(CompatError(err)),
+        }
+    }
+}
+
+impl<S, E> Read for Compat<S>
+where
+    S: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+    E: Debug,
+{
+    /// Blocks until a byte arrives, then reads the bytes that are available without blocking
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        buf[0] = match self.peeked.take() {
+            Some(byte) => byte,
+            None => nb::block!(self.serial.read()).map_err(CompatError)?,
+        };
+
+        let mut n = 1;
+        while n < buf.len() {
+            match self.serial.read() {
+                Ok(byte) => {
+                    buf[n] = byte;
+                    n += 1;
+                }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(err)) => return Err(CompatError(err)),
+            }
+        }
+
+        Ok(n)
+    }
+}
+
+impl<S, E> Write for Compat<S>
+where
+    S: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+    E: Debug,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for byte in buf {
+            nb::block!(self.serial.write(*byte)).map_err(CompatError)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        nb::block!(self.serial.flush()).map_err(CompatError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// An `embedded-hal` 0.2 serial port that has `input` ready and fails once it is empty
+    /// and `broken` is set
+    #[derive(Default)]
+    struct Port {
+        input: VecDeque<u8>,
+        output: Vec<u8>,
+        broken: bool,
+    }
+
+    impl serial::Read<u8> for Port {
+        type Error = &'static str;
+
+        fn read(&mut self) -> nb::Result<u8, &'static str> {
+            match self.input.pop_front() {
+                Some(byte) => Ok(byte),
+                None if self.broken => Err(nb::Error::Other("broken")),
+                None => Err(nb::Error::WouldBlock),
+            }
+        }
+    }
+
+    impl serial::Write<u8> for Port {
+        type Error = &'static str;
+
+        fn write(&mut self, byte: u8) -> nb::Result<(), &'static str> {
+            self.output.push(byte);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> nb::Result<(), &'static str> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reads_and_writes() {
+        let mut port = Port::default();
+        port.input.extend(b"abc".iter());
+        let mut compat = Compat::new(port);
+
+        // the byte that was read to check the port is not lost
+        assert_eq!(compat.read_ready(), Ok(true));
+        let mut buf = [0; 2];
+        assert_eq!(compat.read(&mut buf), Ok(2));
+        assert_eq!(&buf, b"ab");
+        assert_eq!(compat.read(&mut buf), Ok(1));
+        assert_eq!(buf[0], b'c');
+        assert_eq!(compat.read_ready(), Ok(false));
+
+        compat.write_all(b"AT\r\n").unwrap();
+        compat.flush().unwrap();
+        assert_eq!(compat.into_inner().output, b"AT\r\n");
+    }
+
+    #[test]
+    fn errors() {
+        let port = Port {
+            broken: true,
+            ..Port::default()
+        };
+        let mut compat = Compat::new(port);
+
+        let mut buf = [0; 4];
+        assert_eq!(compat.read_ready(), Err(CompatError("broken")));
+        assert_eq!(compat.read(&mut buf), Err(CompatError("broken")));
+        assert_eq!(
+            embedded_io::Error::kind(&CompatError("broken")),
+            ErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn driver() {
+        let mut port = Port::default();
+        port.input
+            .extend(b"AT+GMR\r\r\nAT version:1.7\r\n\r\nOK\r\n".iter());
+        let mut esp01 = crate::esp01(Compat::new(port));
+
+        assert_eq!(esp01.get_version(), Ok(&b"AT version:1.7"[..]));
+        assert_eq!(esp01.serial_mut().serial.output, b"AT+GMR\r\n");
+    }
+}
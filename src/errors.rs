@@ -1,17 +1,216 @@
+use core::fmt;
+
+use embedded_io::ErrorKind;
+
+use crate::parse;
+
 /// A collection of all errors that can occur.
+/// `E` is the error of the serial port.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, PartialEq, Eq)]
-pub enum Error {
+pub enum Error<E = ErrorKind> {
     /// Could not read from serial port.
-    SerialRead,
+    SerialRead(E),
     /// Could not write to serial port.
-    SerialWrite,
-    /// Invalid command or command parameters.
-    CommandError,
+    SerialWrite(E),
+    /// Invalid command or command parameters, with the reason if the firmware reports
+    /// one with `ERR CODE:0x...`.
+    CommandError(Option<ErrorCode>),
     /// The command failed.
     CommandFailed,
     /// Command read back failure
     CommandReadFail,
+    /// The module answered with something that could not be understood.
+    UnexpectedResponse,
+    /// The module is still busy with the previous command (`busy p...`).
+    Busy,
+    /// The module did not respond in time.
+    Timeout,
+    /// The module did not ask for the payload with the `>` prompt.
+    NoSendPrompt,
+    /// The module received fewer bytes than were sent.
+    SendIncomplete,
+    /// The module could not send the data (`SEND FAIL`).
+    SendFailed,
+    /// The data is larger than what can be sent at once.
+    SendTooLarge,
+    /// The response did not fit into the read buffer.
+    ResponseOverflow,
+    /// Received data was dropped because the receive buffer was full.
+    ReceiveOverflow,
+    /// Received a malformed `+IPD` frame.
+    InvalidFrame,
+    /// All links are in use.
+    NoFreeLink,
+    /// The link was closed.
+    LinkClosed,
+    /// The module does not support the operation.
+    Unsupported,
+    /// An SSL connection needs a larger SSL buffer, see `set_ssl_buffer_size`.
+    SslNotConfigured,
+    /// The SSL buffer size is outside of 2048-4096.
+    InvalidSslBufferSize,
+    /// Could not connect to the access point.
+    JoinFailed(JoinError),
+    /// Could not drive the reset pin.
+    ResetPin,
 }
 
-/// A `Result<T, Error>`.
-pub type EResult<T> = Result<T, Error>;
+/// The reasons why connecting to an access point can fail.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum JoinError {
+    /// Connecting timed out.
+    Timeout,
+    /// The password is wrong.
+    WrongPassword,
+    /// The access point could not be found.
+    APNotFound,
+    /// Connecting failed.
+    ConnectFailed,
+}
+
+impl JoinError {
+    /// Decodes a `+CWJAP:<code>`, `+CWJAP_CUR:<code>` or `+CWJAP_DEF:<code>` response line
+    pub(crate) fn parse(line: &[u8]) -> Option<JoinError> {
+        line.strip_prefix(b"+CWJAP")
+            .map(|rest| rest.strip_prefix(b"_CUR").unwrap_or(rest))
+            .map(|rest| rest.strip_prefix(b"_DEF").unwrap_or(rest))
+            .and_then(|rest| rest.strip_prefix(b":"))
+            .and_then(parse::decimal)
+            .and_then(JoinError::from_code)
+    }
+
+    /// Decodes the code of a `+CWJAP:<code>` response
+    fn from_code(code: usize) -> Option<JoinError> {
+        match code {
+            1 => Some(JoinError::Timeout),
+            2 => Some(JoinError::WrongPassword),
+            3 => Some(JoinError::APNotFound),
+            4 => Some(JoinError::ConnectFailed),
+            _ => None,
+        }
+    }
+}
+
+/// The reason for an `ERROR`, reported by newer firmware with `ERR CODE:0x01XXYYYY`
+/// before the `ERROR`. `XX` is the category that is decoded here.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ErrorCode {
+    /// An unspecified error.
+    Common,
+    /// The command did not end with `\r\n`.
+    NoTerminator,
+    /// The command did not start with `AT`.
+    NoAt,
+    /// A parameter is too long or too short.
+    ParameterLength,
+    /// A parameter has the wrong type.
+    ParameterType,
+    /// The command has the wrong number of parameters.
+    ParameterCount,
+    /// A parameter is out of range.
+    InvalidParameter,
+    /// A parameter could not be parsed.
+    ParameterParse,
+    /// The firmware does not support the command.
+    UnsupportedCommand,
+    /// Executing the command failed.
+    ExecutionFailed,
+    /// The previous command is still being processed.
+    Processing,
+    /// The command is not allowed in the current state.
+    Operation,
+    /// A code this driver does not know.
+    Unknown(u32),
+}
+
+impl ErrorCode {
+    /// Decodes the code of an `ERR CODE:0x<code>` line
+    pub(crate) fn parse(line: &[u8]) -> Option<ErrorCode> {
+        let code = line.strip_prefix(b"ERR CODE:0x").and_then(parse::hex)?;
+
+        Some(ErrorCode::from_code(code))
+    }
+
+    fn from_code(code: u32) -> ErrorCode {
+        match (code >> 16) & 0xff {
+            0x01 => ErrorCode::Common,
+            0x02 => ErrorCode::NoTerminator,
+            0x03 => ErrorCode::NoAt,
+            0x04 => ErrorCode::ParameterLength,
+            0x05 => ErrorCode::ParameterType,
+            0x06 => ErrorCode::ParameterCount,
+            0x07 => ErrorCode::InvalidParameter,
+            0x08 => ErrorCode::ParameterParse,
+            0x09 => ErrorCode::UnsupportedCommand,
+            0x0a => ErrorCode::ExecutionFailed,
+            0x0b => ErrorCode::Processing,
+            0x0c => ErrorCode::Operation,
+            _ => ErrorCode::Unknown(code),
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for Error<E> {}
+
+/// A `Result<T, Error<E>>`.
+pub type EResult<T, E = ErrorKind> = Result<T, Error<E>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_errors() {
+        assert_eq!(JoinError::parse(b"+CWJAP:1"), Some(JoinError::Timeout));
+        assert_eq!(
+            JoinError::parse(b"+CWJAP_CUR:2"),
+            Some(JoinError::WrongPassword)
+        );
+        assert_eq!(
+            JoinError::parse(b"+CWJAP_DEF:3"),
+            Some(JoinError::APNotFound)
+        );
+        assert_eq!(
+            JoinError::parse(b"+CWJAP:4"),
+            Some(JoinError::ConnectFailed)
+        );
+        assert_eq!(JoinError::parse(b"+CWJAP:5"), None);
+        // the answer to a query is not an error
+        assert_eq!(
+            JoinError::parse(b"+CWJAP_CUR:\"home\",\"aa:bb:cc:dd:ee:ff\",6,-50"),
+            None
+        );
+    }
+
+    #[test]
+    fn error_codes() {
+        assert_eq!(
+            ErrorCode::parse(b"ERR CODE:0x01090000"),
+            Some(ErrorCode::UnsupportedCommand)
+        );
+        assert_eq!(
+            ErrorCode::parse(b"ERR CODE:0x010b0000"),
+            Some(ErrorCode::Processing)
+        );
+        assert_eq!(
+            ErrorCode::parse(b"ERR CODE:0x01010000"),
+            Some(ErrorCode::Common)
+        );
+        assert_eq!(
+            ErrorCode::parse(b"ERR CODE:0x01200000"),
+            Some(ErrorCode::Unknown(0x0120_0000))
+        );
+        assert_eq!(ErrorCode::parse(b"ERR CODE:0x"), None);
+        assert_eq!(ErrorCode::parse(b"ERROR"), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! Helpers to run the driver on a host with a serial port, e.g. a USB serial adapter.
+
+use std::io::{ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+use embedded_hal as hal;
+use embedded_hal::timer::CountDown;
+
+use void::Void;
+
+use crate::timer::Milliseconds;
+
+/// Makes a `std::io` serial port usable by the driver
+/// The port should have a read timeout, a read that times out counts as no data.
+/// The `embedded-hal` 0.2 serial traits are implemented too.
+pub struct Serial<T: Read + Write>(pub T);
+
+impl<T: Read + Write> embedded_io::ErrorType for Serial<T> {
+    type Error = std::io::Error;
+}
+
+impl<T: Read + Write> embedded_io::Read for Serial<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match self.0.read(buf) {
+            Ok(n) => Ok(n),
+            Err(err) => match err.kind() {
+                ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted => Ok(0),
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+impl<T: Read + Write> embedded_io::ReadReady for Serial<T> {
+    /// A read only waits for the timeout of the port
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+impl<T: Read + Write> embedded_io::Write for Serial<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush()
+    }
+}
+
+impl<T: Read + Write> hal::serial::Read<u8> for Serial<T> {
+    type Error = ErrorKind;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        let mut buffer = [0; 1];
+        let bytes_read = self.0.read(&mut buffer).map_err(translate_io_errors)?;
+        if bytes_read == 1 {
+            Ok(buffer[0])
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<T: Read + Write> hal::serial::Write<u8> for Serial<T> {
+    type Error = ErrorKind;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.0.write(&[word]).map_err(translate_io_errors)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.0.flush().map_err(translate_io_errors)
+    }
+}
+
+/// Helper to convert std::io::Error to the nb::Error
+fn translate_io_errors(err: std::io::Error) -> nb::Error<ErrorKind> {
+    match err.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted => {
+            nb::Error::WouldBlock
+        }
+        err => nb::Error::Other(err),
+    }
+}
+
+/// A timer based on the system clock
+pub struct SysTimer {
+    start: Instant,
+    duration: Duration,
+}
+
+impl SysTimer {
+    pub fn new() -> SysTimer {
+        SysTimer {
+            start: Instant::now(),
+            duration: Duration::from_millis(0),
+        }
+    }
+}
+
+impl Default for SysTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CountDown for SysTimer {
+    type Time = Milliseconds;
+
+    fn start<T>(&mut self, count: T)
+    where
+        T: Into<Milliseconds>,
+    {
+        self.start = Instant::now();
+        self.duration = Duration::from_millis(count.into().0 as u64);
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        if self.start.elapsed() >= self.duration {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
@@ -1,33 +1,121 @@
-//#![no_std]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+// the typestate makes the driver types long
+#![allow(clippy::type_complexity)]
 
+#[macro_use]
+mod log;
+
+#[cfg(feature = "async")]
+pub mod asynch;
+mod buffer;
+#[cfg(feature = "embedded-hal-02")]
+pub mod compat;
 pub mod errors;
+#[cfg(feature = "std")]
+pub mod host;
+#[cfg(feature = "embedded-nal")]
+pub mod nal;
+mod parse;
+#[cfg(test)]
+mod tests;
+pub mod timer;
+pub mod urc;
 
 use core::marker::PhantomData;
+use core::net::{Ipv4Addr, SocketAddrV4};
+use core::ops::{BitOr, RangeInclusive};
 
-use embedded_hal::serial::{Read, Write};
+use embedded_hal::timer::CountDown;
+use embedded_hal_1::delay::DelayNs;
+use embedded_hal_1::digital::OutputPin;
+use embedded_io::{ErrorType, Read, ReadReady, Write};
 
-use nb::block;
+use heapless::spsc::Queue;
+use heapless::{consts, ArrayLength, String, Vec};
 
+use crate::buffer::RingBuffer;
 use crate::errors::EResult;
 use crate::errors::Error;
+use crate::errors::ErrorCode;
+use crate::errors::JoinError;
+use crate::timer::{Milliseconds, NoTimer, Timeouts};
+use crate::urc::Urc;
 
 pub mod atat;
 
 const CR: u8 = b'\r';
 const LF: u8 = b'\n';
 const LINE_END: [u8; 2] = [CR, LF];
-const RESPONSE_END: [u8; 4] = [CR, LF, CR, LF];
-const OK: [u8; 3] = *b"OK\r";
-const ERROR: [u8; 6] = *b"ERROR\r";
-const FAIL: [u8; 5] = *b"FAIL\r";
+const OK: &[u8] = b"OK";
+const ERROR: &[u8] = b"ERROR";
+const FAIL: &[u8] = b"FAIL";
 const AT: [u8; 3] = *b"AT+";
+const ATE: &[u8] = b"ATE";
+const IPD: &[u8] = b"+IPD,";
+const CWLAP: &[u8] = b"+CWLAP:(";
+const CIFSR_STAIP: &[u8] = b"+CIFSR:STAIP,";
+const CIPDOMAIN: &[u8] = b"+CIPDOMAIN:";
+const PROMPT: u8 = b'>';
+const SEND_OK: &[u8] = b"SEND OK";
+const SEND_FAIL: &[u8] = b"SEND FAIL";
+const RECV: &[u8] = b"Recv ";
+const READY: &[u8] = b"ready";
+const BYTES: &[u8] = b" bytes";
+
+/// Room kept free in the read buffer to recognize the end of a response that does not fit
+const LINE_RESERVE: usize = 16;
+/// Number of bytes read from the serial port at once
+const INPUT_LEN: usize = 64;
+
+/// Number of links the module supports in multi-connection mode
+pub const MAX_LINKS: usize = 5;
+
+const LINK_IDS: [&str; MAX_LINKS] = ["0", "1", "2", "3", "4"];
 
-pub struct Esp01<S, MODE> {
+/// Maximum number of bytes the module accepts in a single `AT+CIPSEND`
+pub const MAX_SEND_LEN: usize = 2048;
+
+/// Sizes of the SSL buffer the module accepts
+const SSL_BUFFER_SIZE: RangeInclusive<u16> = 2048..=4096;
+
+/// Ends transparent transmission when it arrives as a packet of its own
+const PASSTHROUGH_EXIT: &[u8] = b"+++";
+/// Silence around `+++` so the module does not take it as data
+const PASSTHROUGH_GUARD_MS: u32 = 20;
+/// Time the module needs after `+++` before it accepts commands
+const PASSTHROUGH_EXIT_MS: u32 = 1_000;
+/// How long the reset pin is held low for a hardware reset
+const RESET_PULSE_MS: u32 = 10;
+
+/// The driver, in the state `MODE`
+/// `S` is the serial port, an `embedded-io` byte stream. Ports that implement the
+/// `embedded-hal` 0.2 serial traits can be wrapped in `compat::Compat`.
+/// `N` is the size of the buffer for responses, `RX` the size of the receive buffer of each link.
+/// `T` is the timer that limits how long the driver waits for a response.
+pub struct Esp01<S, MODE, const N: usize = 512, const RX: usize = 256, T = NoTimer> {
     serial: S,
-    read_buf: [u8; 512],
+    timer: T,
+    timeouts: Timeouts,
+    input: [u8; INPUT_LEN],
+    input_start: usize,
+    input_end: usize,
+    read_buf: [u8; N],
+    rx: [RingBuffer<RX>; MAX_LINKS],
+    /// The datagrams in the receive buffers of UDP links
+    frames: [Queue<Datagram, consts::U8>; MAX_LINKS],
+    /// Links that carry datagrams, their data is received one frame at a time
+    datagrams: u8,
+    links: u8,
+    accepted: u8,
+    scan_fields: ScanFields,
+    ssl_buffer_size: Option<u16>,
+    /// Whether the module echoes commands, it does after a restart
+    echo: bool,
+    urcs: Queue<Urc, consts::U8>,
     _mode: PhantomData<MODE>,
 }
 
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Mode {
     StationMode,
@@ -35,19 +123,221 @@ pub enum Mode {
     StationAndAPMode,
 }
 
+impl Mode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mode::StationMode => "1",
+            Mode::SoftAPMode => "2",
+            Mode::StationAndAPMode => "3",
+        }
+    }
+
+    fn from_code(code: usize) -> Option<Mode> {
+        match code {
+            1 => Some(Mode::StationMode),
+            2 => Some(Mode::SoftAPMode),
+            3 => Some(Mode::StationAndAPMode),
+            _ => None,
+        }
+    }
+}
+
+/// A Wi-Fi mode that can be set with `set_mode`
+pub trait WifiMode {
+    /// The state of the driver in this mode
+    type State;
+
+    fn mode(&self) -> Mode;
+}
+
+/// Selects the station mode
+pub struct Station;
+/// Selects the SoftAP mode
+pub struct SoftAP;
+/// Selects the combined station and SoftAP mode
+pub struct StationAndAP;
+
+impl WifiMode for Station {
+    type State = StationMode<APDisconnected>;
+
+    fn mode(&self) -> Mode {
+        Mode::StationMode
+    }
+}
+
+impl WifiMode for SoftAP {
+    type State = SoftAPMode;
+
+    fn mode(&self) -> Mode {
+        Mode::SoftAPMode
+    }
+}
+
+impl WifiMode for StationAndAP {
+    type State = StationAndAPMode<APDisconnected>;
+
+    fn mode(&self) -> Mode {
+        Mode::StationAndAPMode
+    }
+}
+
 pub struct UnknownMode {}
-pub struct StationMode<MODE> {
-    _mode: PhantomData<MODE>,
+pub struct StationMode<MODE, SOFTAP = SoftAPOff> {
+    _mode: PhantomData<(MODE, SOFTAP)>,
 }
+/// SoftAP mode, `LINK` is `MultipleLinks` once multi-connection mode is enabled
+pub struct SoftAPMode<LINK = LinkDisconnected> {
+    _mode: PhantomData<LINK>,
+}
+
+/// Station mode with the SoftAP enabled as well
+pub type StationAndAPMode<MODE> = StationMode<MODE, SoftAPOn>;
+
+pub struct SoftAPOn {}
+pub struct SoftAPOff {}
+
+/// Marks the modes in which the SoftAP is enabled
+pub trait SoftAPEnabled {}
+
+impl<LINK> SoftAPEnabled for SoftAPMode<LINK> {}
+impl<MODE> SoftAPEnabled for StationMode<MODE, SoftAPOn> {}
+
+/// Marks the modes in which the module accepts commands,
+/// during transparent transmission only `exit_passthrough` is available
+pub trait CommandMode {}
+
+impl CommandMode for UnknownMode {}
+impl CommandMode for SoftAPMode {}
+impl CommandMode for SoftAPMode<MultipleLinks> {}
+impl<SAP> CommandMode for StationMode<APDisconnected, SAP> {}
+impl<SAP> CommandMode for StationMode<APDisconnected<MultipleLinks>, SAP> {}
+impl<SAP> CommandMode for StationMode<APConnected<LinkDisconnected>, SAP> {}
+impl<SAP> CommandMode for StationMode<APConnected<LinkConnected>, SAP> {}
+impl<SAP> CommandMode for StationMode<APConnected<MultipleLinks>, SAP> {}
 
 pub struct APConnected<LINK> {
     _mode: PhantomData<LINK>,
 }
-pub struct APDisconnected {}
+/// Not connected to an access point, `LINK` is `MultipleLinks` once multi-connection
+/// mode is enabled
+pub struct APDisconnected<LINK = LinkDisconnected> {
+    _mode: PhantomData<LINK>,
+}
 
 pub struct LinkConnected {}
 pub struct LinkDisconnected {}
+/// Transparent transmission of a single connection (`AT+CIPMODE=1`),
+/// the serial port carries the data of the link instead of commands
+pub struct Passthrough {}
+/// Multi-connection mode (`AT+CIPMUX=1`), links are addressed by their `LinkId`
+pub struct MultipleLinks {}
+
+/// Marks the link states of a station connected to an access point,
+/// `Closed` is the state that is left when it disconnects
+pub trait LinkMode {
+    type Closed;
+}
+
+impl LinkMode for LinkDisconnected {
+    type Closed = LinkDisconnected;
+}
+impl LinkMode for LinkConnected {
+    type Closed = LinkDisconnected;
+}
+impl LinkMode for MultipleLinks {
+    type Closed = MultipleLinks;
+}
+
+/// Marks the modes without an open link in which multi-connection mode can be enabled,
+/// `Enabled` is the mode with multi-connection mode enabled
+pub trait MultipleLinksDisabled {
+    type Enabled;
+}
+
+impl MultipleLinksDisabled for SoftAPMode {
+    type Enabled = SoftAPMode<MultipleLinks>;
+}
+impl<SAP> MultipleLinksDisabled for StationMode<APConnected<LinkDisconnected>, SAP> {
+    type Enabled = StationMode<APConnected<MultipleLinks>, SAP>;
+}
+impl MultipleLinksDisabled for StationAndAPMode<APDisconnected> {
+    type Enabled = StationAndAPMode<APDisconnected<MultipleLinks>>;
+}
+
+/// Marks the modes in which links are opened in multi-connection mode and the server
+/// can be started, `Disabled` is the mode with multi-connection mode disabled
+pub trait MultipleLinksEnabled {
+    type Disabled;
+}
+
+impl MultipleLinksEnabled for SoftAPMode<MultipleLinks> {
+    type Disabled = SoftAPMode;
+}
+impl<SAP> MultipleLinksEnabled for StationMode<APConnected<MultipleLinks>, SAP> {
+    type Disabled = StationMode<APConnected<LinkDisconnected>, SAP>;
+}
+impl MultipleLinksEnabled for StationAndAPMode<APDisconnected<MultipleLinks>> {
+    type Disabled = StationAndAPMode<APDisconnected>;
+}
 
+/// The state of a link, see `check_link`
+pub enum LinkState<C, D> {
+    Connected(C),
+    Closed(D),
+}
+
+/// The state `probe` found the module in
+pub enum Probe<S, const N: usize, const RX: usize, T> {
+    Station(StationProbe<S, SoftAPOff, N, RX, T>),
+    StationAndAP(StationProbe<S, SoftAPOn, N, RX, T>),
+    SoftAP(Esp01<S, SoftAPMode, N, RX, T>),
+    /// SoftAP in multi-connection mode
+    SoftAPMultipleLinks(Esp01<S, SoftAPMode<MultipleLinks>, N, RX, T>),
+}
+
+/// The state `probe` found the station in
+pub enum StationProbe<S, SAP, const N: usize, const RX: usize, T> {
+    /// Not connected to an access point
+    Disconnected(Esp01<S, StationMode<APDisconnected, SAP>, N, RX, T>),
+    /// Not connected to an access point, in multi-connection mode
+    DisconnectedMultipleLinks(Esp01<S, StationMode<APDisconnected<MultipleLinks>, SAP>, N, RX, T>),
+    /// Connected to an access point, without an open connection
+    Joined(Esp01<S, StationMode<APConnected<LinkDisconnected>, SAP>, N, RX, T>),
+    /// Connected to an access point with an open connection
+    Linked(Esp01<S, StationMode<APConnected<LinkConnected>, SAP>, N, RX, T>),
+    /// Connected to an access point in multi-connection mode
+    MultipleLinks(Esp01<S, StationMode<APConnected<MultipleLinks>, SAP>, N, RX, T>),
+}
+
+/// Identifies a link in multi-connection mode
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct LinkId(u8);
+
+impl LinkId {
+    /// Returns the link with the given id, if it is in the range 0-4
+    pub fn new(id: u8) -> Option<LinkId> {
+        if (id as usize) < MAX_LINKS {
+            Some(LinkId(id))
+        } else {
+            None
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        self.0
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        LINK_IDS[self.0 as usize]
+    }
+
+    fn mask(&self) -> u8 {
+        1 << self.0
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Persist {
     DontSave,
@@ -63,6 +353,7 @@ impl Persist {
     }
 }
 
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum QueryMode {
     Current,
@@ -78,6 +369,7 @@ impl QueryMode {
     }
 }
 
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum ConnectionMode {
     UDP,
@@ -93,275 +385,2395 @@ impl ConnectionMode {
             ConnectionMode::SSL => "SSL",
         }
     }
-}
 
-pub fn esp01<S, E>(serial: S) -> Esp01<S, UnknownMode>
-where
-    S: Read<u8, Error = E> + Write<u8, Error = E>,
-{
-    Esp01 {
-        serial,
-        read_buf: [0; 512],
-        _mode: PhantomData,
+    fn from_name(name: &[u8]) -> Option<ConnectionMode> {
+        match name {
+            b"UDP" => Some(ConnectionMode::UDP),
+            b"TCP" => Some(ConnectionMode::TCP),
+            b"SSL" => Some(ConnectionMode::SSL),
+            _ => None,
+        }
     }
 }
 
-impl<S, E, MODE> Esp01<S, MODE>
-where
-    S: Read<u8, Error = E> + Write<u8, Error = E>,
-{
-    /// Writes a byte to the serial port
-    fn write_byte(&mut self, byte: u8) -> EResult<()> {
-        block!(self.serial.write(byte)).map_err(|_| Error::SerialWrite)
-    }
-
-    /// Reads a byte from the serial port
-    fn read_byte(&mut self) -> EResult<u8> {
-        block!(self.serial.read()).map_err(|_| Error::SerialRead)
-    }
+/// How a connection is closed (`AT+CIPCLOSEMODE`)
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum CloseMode {
+    /// The connection is closed with a FIN
+    Graceful,
+    /// The connection is aborted with a RST
+    Abort,
+}
 
-    /// Writes line end sequence
-    fn write_line_end(&mut self) -> EResult<()> {
-        self.write_byte(CR)?;
-        self.write_byte(LF)
+impl CloseMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloseMode::Graceful => "0",
+            CloseMode::Abort => "1",
+        }
     }
+}
 
-    /// Reads the response for a command
-    pub fn read_response(&mut self) -> EResult<&[u8]> {
-        let mut i = 0;
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Encryption {
+    Open,
+    WEP,
+    /// WPA_PSK
+    WPA,
+    /// WPA2_PSK
+    WPA2,
+    /// WPA_WPA2_PSK
+    WPAWPA2,
+    /// WPA2 Enterprise, only reported by scans
+    WPA2Enterprise,
+}
 
-        while i < self.read_buf.len() {
-            match self.read_byte()? {
-                LF if i > 2 && self.read_buf[(i - 3)..i] == OK => {
-                    if i > 6 && self.read_buf[(i - 7)..(i - 3)] == RESPONSE_END {
-                        return Ok(&self.read_buf[0..(i - 7)]);
-                    } else {
-                        return Ok(&self.read_buf[0..(i - 3)]);
-                    }
-                }
-                LF if i > 5 && self.read_buf[(i - 6)..i] == ERROR => {
-                    return Err(Error::CommandError);
-                }
-                LF if i > 4 && self.read_buf[(i - 5)..i] == FAIL => {
-                    return Err(Error::CommandFailed);
-                }
-                other => {
-                    self.read_buf[i] = other;
-                    i += 1;
-                }
-            }
+impl Encryption {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encryption::Open => "0",
+            Encryption::WEP => "1",
+            Encryption::WPA => "2",
+            Encryption::WPA2 => "3",
+            Encryption::WPAWPA2 => "4",
+            Encryption::WPA2Enterprise => "5",
         }
-
-        Ok(&self.read_buf[0..i])
     }
 
-    /// Reads a byte and checks that it is the expected byte
-    fn read_byte_back(&mut self, byte: u8) -> EResult<()> {
-        if self.read_byte()? == byte {
-            Ok(())
-        } else {
-            Err(Error::CommandReadFail)
+    fn from_code(code: usize) -> Option<Encryption> {
+        match code {
+            0 => Some(Encryption::Open),
+            1 => Some(Encryption::WEP),
+            2 => Some(Encryption::WPA),
+            3 => Some(Encryption::WPA2),
+            4 => Some(Encryption::WPAWPA2),
+            5 => Some(Encryption::WPA2Enterprise),
+            _ => None,
         }
     }
+}
 
-    /// Checks that the response starts with the command that was sent
-    fn read_command_back(&mut self, command: &[&str], query: bool) -> EResult<()> {
-        for b in AT.iter() {
-            self.read_byte_back(*b)?;
-        }
-        for part in command {
-            for b in part.as_bytes() {
-                self.read_byte_back(*b)?;
-            }
-        }
-        if query {
-            self.read_byte_back(b'?')?;
+/// The encryption of the SoftAP, the subset of `Encryption` that `AT+CWSAP` accepts
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ApEncryption {
+    Open,
+    /// WPA_PSK
+    WPA,
+    /// WPA2_PSK
+    WPA2,
+    /// WPA_WPA2_PSK
+    WPAWPA2,
+}
+
+impl ApEncryption {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApEncryption::Open => "0",
+            ApEncryption::WPA => "2",
+            ApEncryption::WPA2 => "3",
+            ApEncryption::WPAWPA2 => "4",
         }
+    }
+}
 
-        self.read_byte_back(CR)?;
-        self.read_byte_back(CR)?;
-        self.read_byte_back(LF)
+/// Optional parameters for connecting to an access point
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub struct JoinOptions<'a> {
+    bssid: Option<&'a str>,
+    pci_en: Option<bool>,
+    reconnect_interval: Option<u16>,
+    listen_interval: Option<u8>,
+}
+
+impl<'a> JoinOptions<'a> {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// Sends a command
-    fn send_command(&mut self, command: &[&str]) -> EResult<()> {
-        for b in AT.iter() {
-            self.write_byte(*b)?;
-        }
-        for part in command {
-            for b in part.as_bytes() {
-                self.write_byte(*b)?;
-            }
-        }
-        self.write_line_end()?;
+    /// Only connects to the access point with this MAC address, e.g. `"aa:bb:cc:dd:ee:ff"`
+    pub fn bssid(mut self, bssid: &'a str) -> Self {
+        self.bssid = Some(bssid);
+        self
+    }
 
-        self.read_command_back(command, false)
+    /// Refuses to connect to open and WEP access points if enabled
+    pub fn pci_en(mut self, enable: bool) -> Self {
+        self.pci_en = Some(enable);
+        self
     }
 
-    /// Sends a query
-    fn send_query(&mut self, command: &[&str]) -> EResult<&[u8]> {
-        for b in AT.iter() {
-            self.write_byte(*b)?;
-        }
-        for part in command {
-            for b in part.as_bytes() {
-                self.write_byte(*b)?;
-            }
-        }
-        self.write_byte(b'?')?;
-        self.write_line_end()?;
-        self.read_command_back(command, true)?;
-        self.read_byte_back(b'+')?;
-        for part in command {
-            for b in part.as_bytes() {
-                self.read_byte_back(*b)?;
-            }
-        }
-        self.read_byte_back(b':')?;
-        self.read_response()
+    /// Seconds between attempts to reconnect after the connection was lost (0-7200)
+    /// An interval of 0 disables reconnecting.
+    pub fn reconnect_interval(mut self, seconds: u16) -> Self {
+        self.reconnect_interval = Some(seconds);
+        self
     }
 
-    /// Gets ESP01 version information
-    pub fn get_version(&mut self) -> EResult<&[u8]> {
-        self.send_command(&["GMR"])?;
-        self.read_response()
+    /// Interval in beacon intervals at which the station listens to beacons (1-100)
+    pub fn listen_interval(mut self, interval: u8) -> Self {
+        self.listen_interval = Some(interval);
+        self
     }
+}
 
-    /// Sets the Wi-Fi mode
-    pub fn set_mode(
-        mut self,
-        mode: Mode,
-        persist: Persist,
-    ) -> EResult<Esp01<S, StationMode<APDisconnected>>> {
-        let mode_str = match mode {
-            Mode::StationMode => "1",
-            Mode::SoftAPMode => "2",
-            Mode::StationAndAPMode => "3",
-        };
+/// Where and how to connect, with optional parameters of the connection
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct ConnectOptions<'a> {
+    connection_mode: ConnectionMode,
+    host: Host<'a>,
+    port: u16,
+    keepalive: Option<u16>,
+    local_port: Option<(u16, UdpMode)>,
+    sni: Option<&'a str>,
+}
 
-        self.send_command(&["CWMODE", persist.as_str(), mode_str])?;
-        self.read_response()?;
+impl<'a> ConnectOptions<'a> {
+    pub fn new<H: Into<Host<'a>>>(connection_mode: ConnectionMode, host: H, port: u16) -> Self {
+        ConnectOptions {
+            connection_mode,
+            host: host.into(),
+            port,
+            keepalive: None,
+            local_port: None,
+            sni: None,
+        }
+    }
 
-        Ok(Esp01 {
-            serial: self.serial,
-            read_buf: self.read_buf,
-            _mode: PhantomData,
-        })
+    /// Seconds after which an idle TCP or SSL connection is checked (1-7200)
+    /// An interval of 0 disables the keepalive. Ignored for UDP.
+    pub fn keepalive(mut self, seconds: u16) -> Self {
+        self.keepalive = Some(seconds);
+        self
     }
 
-    /// Sets the MAC address for the station
-    pub fn set_station_mac(&mut self, mac: &str, persist: Persist) -> EResult<&[u8]> {
-        self.send_command(&["CIPSTAMAC", persist.as_str(), "\"", mac, "\""])?;
-        self.read_response()
+    /// The local port of a UDP connection and whether the remote peer can change
+    /// Ignored for TCP and SSL.
+    pub fn local_port(mut self, port: u16, mode: UdpMode) -> Self {
+        self.local_port = Some((port, mode));
+        self
     }
 
-    /// Gets the MAC address for the station
-    pub fn get_station_mac(&mut self, query_mode: QueryMode) -> EResult<&[u8]> {
-        let r = self.send_query(&["CIPSTAMAC", query_mode.as_str()])?;
-        Ok(&r[1..(r.len() - 1)])
+    /// The server name an SSL connection sends in the handshake (`AT+CIPSSLCSNI`)
+    /// Needs a firmware that supports SNI. Ignored for TCP and UDP.
+    pub fn sni(mut self, name: &'a str) -> Self {
+        self.sni = Some(name);
+        self
     }
 }
 
-impl<S, E> Esp01<S, StationMode<APDisconnected>>
-where
-    S: Read<u8, Error = E> + Write<u8, Error = E>,
-{
-    //TODO deal with the optional args
-    /// Connects to an access point
-    pub fn connect_ap(
-        mut self,
-        ssid: &str,
-        password: &str,
-        persist: Persist,
-    ) -> EResult<Esp01<S, StationMode<APConnected<LinkDisconnected>>>> {
-        self.send_command(&[
-            "CWJAP",
-            persist.as_str(),
-            "\"",
-            ssid,
-            "\",\"",
-            password,
-            "\"",
-        ])?;
-        self.read_response()?;
+/// Whether the remote peer of a UDP connection changes to the sender of received data
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum UdpMode {
+    /// The remote peer stays the same
+    Fixed,
+    /// The remote peer changes once, to the first sender
+    ChangeOnce,
+    /// The remote peer changes to each sender
+    ChangeAlways,
+}
 
-        Ok(Esp01 {
-            serial: self.serial,
-            read_buf: self.read_buf,
-            _mode: PhantomData,
-        })
+impl UdpMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UdpMode::Fixed => "0",
+            UdpMode::ChangeOnce => "1",
+            UdpMode::ChangeAlways => "2",
+        }
     }
 }
 
-impl<S, L, E> Esp01<S, StationMode<APConnected<L>>>
-where
-    S: Read<u8, Error = E> + Write<u8, Error = E>,
-{
-    /// Disconnects from the access point
-    pub fn disconnect_ap(mut self) -> EResult<Esp01<S, StationMode<APDisconnected>>> {
-        self.send_command(&["CWQAP"])?;
-        self.read_response()?;
+/// Which certificates an SSL connection checks (`AT+CIPSSLCCONF`)
+/// The certificates have to be flashed to the module beforehand.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum SslAuth {
+    /// No certificates are checked
+    None,
+    /// The module presents its certificate to the server
+    ClientCertificate,
+    /// The module verifies the server against its CA certificate
+    VerifyServer,
+    /// Both, the module presents its certificate and verifies the server
+    Mutual,
+}
 
-        Ok(Esp01 {
-            serial: self.serial,
-            read_buf: self.read_buf,
-            _mode: PhantomData,
-        })
+impl SslAuth {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SslAuth::None => "0",
+            SslAuth::ClientCertificate => "1",
+            SslAuth::VerifyServer => "2",
+            SslAuth::Mutual => "3",
+        }
     }
+}
 
-    /// Enables/Disables autoconnection to the accesspoint on power up
-    /// This configuration is saved in flash.
-    pub fn autoconnect_ap(&mut self, enable: bool) -> EResult<()> {
-        let param = match enable {
-            true => "1",
-            false => "0",
-        };
+/// The settings of the UART of the module, see `configure_uart`
+/// The default frame format is 8N1 without flow control.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct UartConfig {
+    baud_rate: u32,
+    data_bits: DataBits,
+    stop_bits: StopBits,
+    parity: Parity,
+    flow_control: FlowControl,
+}
 
-        self.send_command(&["CWAUTOCONN=", param])?;
-        self.read_response()?;
+impl UartConfig {
+    /// Baud rate of the UART (110-4608000)
+    pub fn new(baud_rate: u32) -> Self {
+        UartConfig {
+            baud_rate,
+            data_bits: DataBits::Eight,
+            stop_bits: StopBits::One,
+            parity: Parity::None,
+            flow_control: FlowControl::None,
+        }
+    }
 
-        Ok(())
+    pub fn data_bits(mut self, data_bits: DataBits) -> Self {
+        self.data_bits = data_bits;
+        self
     }
-}
 
-impl<S, E> Esp01<S, StationMode<APConnected<LinkDisconnected>>>
-where
-    S: Read<u8, Error = E> + Write<u8, Error = E>,
-{
-    /// Connects to an endpoint
-    pub fn connect(
-        mut self,
-        connection_mode: ConnectionMode,
-        ip: &str,
-        port: &str,
-    ) -> EResult<Esp01<S, StationMode<APConnected<LinkConnected>>>> {
-        self.send_command(&[
-            "CIPSTART=",
-            "\"",
-            connection_mode.as_str(),
-            "\",\"",
-            ip,
-            "\",",
-            port,
-        ])?;
+    pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = stop_bits;
+        self
+    }
 
-        self.read_response()?;
-        Ok(Esp01 {
-            serial: self.serial,
-            read_buf: self.read_buf,
-            _mode: PhantomData,
-        })
+    pub fn parity(mut self, parity: Parity) -> Self {
+        self.parity = parity;
+        self
+    }
+
+    /// The RTS and CTS pins have to be connected for the flow control that is enabled
+    pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.flow_control = flow_control;
+        self
     }
 }
 
-impl<S, E> Esp01<S, StationMode<APConnected<LinkConnected>>>
-where
-    S: Read<u8, Error = E> + Write<u8, Error = E>,
-{
-    /// Sends data over the connection
-    pub fn send(&mut self, data: &[u8]) -> EResult<()> {
-        self.send_command(&["CIPSEND="])?;
-        //write!(self.serial, "{}", data.len());
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum DataBits {
+    Five,
+    Six,
+    Seven,
+    Eight,
+}
+
+impl DataBits {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataBits::Five => "5",
+            DataBits::Six => "6",
+            DataBits::Seven => "7",
+            DataBits::Eight => "8",
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum StopBits {
+    One,
+    OneAndHalf,
+    Two,
+}
+
+impl StopBits {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopBits::One => "1",
+            StopBits::OneAndHalf => "2",
+            StopBits::Two => "3",
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+impl Parity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Parity::None => "0",
+            Parity::Odd => "1",
+            Parity::Even => "2",
+        }
+    }
+}
+
+/// Hardware flow control of the UART
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum FlowControl {
+    None,
+    /// The module signals with RTS when it can not take more data
+    Rts,
+    /// The module stops sending while CTS is high
+    Cts,
+    RtsCts,
+}
+
+impl FlowControl {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlowControl::None => "0",
+            FlowControl::Rts => "1",
+            FlowControl::Cts => "2",
+            FlowControl::RtsCts => "3",
+        }
+    }
+}
+
+/// An access point found by a scan
+/// Fields that were not selected with `set_scan_options` are `None`.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct AccessPoint {
+    pub encryption: Option<Encryption>,
+    pub ssid: Option<String<consts::U32>>,
+    /// Signal strength in dBm
+    pub rssi: Option<i8>,
+    pub mac: Option<[u8; 6]>,
+    pub channel: Option<u8>,
+}
+
+impl AccessPoint {
+    /// Parses a `+CWLAP:(<ecn>,"<ssid>",<rssi>,"<mac>",<channel>,...)` line
+    fn parse(line: &[u8], fields: ScanFields) -> Option<AccessPoint> {
+        let line = line.strip_prefix(CWLAP)?.strip_suffix(b")")?;
+        let mut params = parse::params(line);
+        let mut next = |field: ScanFields| match fields.contains(field) {
+            true => params.next(),
+            false => None,
+        };
+
+        Some(AccessPoint {
+            encryption: next(ScanFields::ENCRYPTION)
+                .and_then(parse::decimal)
+                .and_then(Encryption::from_code),
+            ssid: next(ScanFields::SSID).and_then(parse::unescape),
+            rssi: next(ScanFields::RSSI)
+                .and_then(parse::signed)
+                .map(|rssi| rssi as i8),
+            mac: next(ScanFields::MAC).and_then(parse::mac),
+            channel: next(ScanFields::CHANNEL)
+                .and_then(parse::decimal)
+                .map(|channel| channel as u8),
+        })
+    }
+}
+
+/// The fields of an access point that a scan reports
+/// Fields can be combined with `|`.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct ScanFields(u16);
+
+impl ScanFields {
+    pub const ENCRYPTION: ScanFields = ScanFields(1);
+    pub const SSID: ScanFields = ScanFields(1 << 1);
+    pub const RSSI: ScanFields = ScanFields(1 << 2);
+    pub const MAC: ScanFields = ScanFields(1 << 3);
+    pub const CHANNEL: ScanFields = ScanFields(1 << 4);
+    pub const ALL: ScanFields = ScanFields(0x1f);
+
+    pub fn contains(&self, fields: ScanFields) -> bool {
+        self.0 & fields.0 == fields.0
+    }
+}
+
+impl BitOr for ScanFields {
+    type Output = ScanFields;
+
+    fn bitor(self, rhs: ScanFields) -> ScanFields {
+        ScanFields(self.0 | rhs.0)
+    }
+}
+
+/// A station connected to the SoftAP
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct ConnectedStation {
+    pub ip: Ipv4Addr,
+    pub mac: [u8; 6],
+}
+
+/// The state of the station and its connections (`STATUS:<n>`)
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ConnectionStatus {
+    /// The station is not initialized
+    Inactive,
+    /// The station did not start to connect to an access point
+    Idle,
+    /// Connected to an access point and got an IP address
+    GotIp,
+    /// At least one link is open
+    Connected,
+    /// All links were closed
+    Disconnected,
+    /// Not connected to an access point
+    NoAp,
+    /// A status this driver does not know
+    Unknown(u8),
+}
+
+impl ConnectionStatus {
+    fn from_code(code: usize) -> ConnectionStatus {
+        match code {
+            0 => ConnectionStatus::Inactive,
+            1 => ConnectionStatus::Idle,
+            2 => ConnectionStatus::GotIp,
+            3 => ConnectionStatus::Connected,
+            4 => ConnectionStatus::Disconnected,
+            5 => ConnectionStatus::NoAp,
+            code => ConnectionStatus::Unknown(code as u8),
+        }
+    }
+}
+
+/// An open link reported by `AT+CIPSTATUS`
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct LinkStatus {
+    pub link: LinkId,
+    pub connection_mode: ConnectionMode,
+    pub remote_ip: Ipv4Addr,
+    pub remote_port: u16,
+    pub local_port: u16,
+    /// Whether the module accepted the connection as a server
+    pub server: bool,
+}
+
+impl LinkStatus {
+    /// Parses a `+CIPSTATUS:<id>,"<type>","<ip>",<remote port>,<local port>,<tetype>` line
+    fn parse(line: &[u8]) -> Option<LinkStatus> {
+        let mut params = parse::params(line.strip_prefix(b"+CIPSTATUS:")?);
+        let port = |param: &[u8]| parse::decimal(param).map(|port| port as u16);
+
+        Some(LinkStatus {
+            link: params
+                .next()
+                .and_then(parse::decimal)
+                .and_then(|id| LinkId::new(id as u8))?,
+            connection_mode: params
+                .next()
+                .and_then(parse::unquote)
+                .and_then(ConnectionMode::from_name)?,
+            remote_ip: params.next().and_then(parse::ipv4)?,
+            remote_port: params.next().and_then(port)?,
+            local_port: params.next().and_then(port)?,
+            server: params.next()? == b"1",
+        })
+    }
+}
+
+/// The status of the station and its open links
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Status {
+    pub status: ConnectionStatus,
+    /// The open links, by their id
+    pub links: [Option<LinkStatus>; MAX_LINKS],
+}
+
+/// The host of an endpoint to connect to
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Host<'a> {
+    Ip(Ipv4Addr),
+    /// A domain name, the module resolves it when connecting
+    Name(&'a str),
+}
+
+impl From<Ipv4Addr> for Host<'_> {
+    fn from(ip: Ipv4Addr) -> Self {
+        Host::Ip(ip)
+    }
+}
+
+impl<'a> From<&'a str> for Host<'a> {
+    fn from(name: &'a str) -> Self {
+        Host::Name(name)
+    }
+}
+
+/// The IP configuration of the station
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct IpConfig {
+    pub ip: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+}
+
+/// A decimal number formatted for use as a command parameter
+struct Decimal {
+    buf: [u8; 10],
+    start: usize,
+}
+
+impl Decimal {
+    fn new(mut n: usize) -> Self {
+        let mut buf = [0; 10];
+        let mut start = buf.len();
+        loop {
+            start -= 1;
+            buf[start] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        Decimal { buf, start }
+    }
+
+    fn as_str(&self) -> &str {
+        // only ASCII digits are ever written to the buffer
+        core::str::from_utf8(&self.buf[self.start..]).unwrap_or("")
+    }
+}
+
+/// A datagram in the receive buffer of a UDP link
+struct Datagram {
+    len: u16,
+    /// Where the datagram came from, if the module reported it
+    source: Option<SocketAddrV4>,
+}
+
+/// An IPv4 address formatted for use as a command parameter
+struct IpString {
+    buf: [u8; 15],
+    len: usize,
+}
+
+impl IpString {
+    fn new(ip: Ipv4Addr) -> Self {
+        let mut buf = [0; 15];
+        let mut len = 0;
+        for (i, octet) in ip.octets().iter().enumerate() {
+            if i > 0 {
+                buf[len] = b'.';
+                len += 1;
+            }
+            let octet = Decimal::new(*octet as usize);
+            let digits = octet.as_str().as_bytes();
+            buf[len..(len + digits.len())].copy_from_slice(digits);
+            len += digits.len();
+        }
+
+        IpString { buf, len }
+    }
+
+    fn as_str(&self) -> &str {
+        // only ASCII digits and dots are ever written to the buffer
+        core::str::from_utf8(&self.buf[0..self.len]).unwrap_or("")
+    }
+}
+
+/// A host formatted for use as a command parameter
+enum HostString<'a> {
+    Ip(IpString),
+    Name(&'a str),
+}
+
+impl<'a> HostString<'a> {
+    fn new(host: Host<'a>) -> Self {
+        match host {
+            Host::Ip(ip) => HostString::Ip(IpString::new(ip)),
+            Host::Name(name) => HostString::Name(name),
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            HostString::Ip(ip) => ip.as_str(),
+            HostString::Name(name) => name,
+        }
+    }
+}
+
+/// The parameters of `AT+CIPSTART` formatted for use in a command
+struct ConnectCommand<'a> {
+    link: Option<LinkId>,
+    options: ConnectOptions<'a>,
+    host: HostString<'a>,
+    port: Decimal,
+    extra: Decimal,
+}
+
+impl<'a> ConnectCommand<'a> {
+    /// `link` is `None` in single connection mode
+    fn new(link: Option<LinkId>, options: &ConnectOptions<'a>) -> Self {
+        let extra = match (options.connection_mode, options.local_port) {
+            (ConnectionMode::UDP, Some((port, _))) => port,
+            (ConnectionMode::UDP, None) => 0,
+            _ => options.keepalive.unwrap_or(0),
+        };
+
+        ConnectCommand {
+            link,
+            options: *options,
+            host: HostString::new(options.host),
+            port: Decimal::new(options.port as usize),
+            extra: Decimal::new(extra as usize),
+        }
+    }
+
+    /// Returns the local port of a UDP connection or the keepalive of a TCP connection,
+    /// with the separator in front, if it was set
+    fn extra(&self) -> (&str, &str) {
+        let set = match self.options.connection_mode {
+            ConnectionMode::UDP => self.options.local_port.is_some(),
+            _ => self.options.keepalive.is_some(),
+        };
+
+        if set {
+            (",", self.extra.as_str())
+        } else {
+            ("", "")
+        }
+    }
+
+    /// The parts of `AT+CIPSTART`
+    fn parts(&self) -> [&str; 13] {
+        let (link, link_end) = match self.link {
+            Some(link) => (link.as_str(), ","),
+            None => ("", ""),
+        };
+        let (extra_start, extra) = self.extra();
+        let (mode_start, mode) = match (self.options.connection_mode, self.options.local_port) {
+            (ConnectionMode::UDP, Some((_, mode))) => (",", mode.as_str()),
+            _ => ("", ""),
+        };
+
+        [
+            "CIPSTART=",
+            link,
+            link_end,
+            "\"",
+            self.options.connection_mode.as_str(),
+            "\",\"",
+            self.host.as_str(),
+            "\",",
+            self.port.as_str(),
+            extra_start,
+            extra,
+            mode_start,
+            mode,
+        ]
+    }
+
+    /// The parts of `AT+CIPSSLCSNI`, if the server name of an SSL connection was set
+    fn sni_parts(&self) -> Option<[&str; 6]> {
+        let (link, link_end) = match self.link {
+            Some(link) => (link.as_str(), ","),
+            None => ("", ""),
+        };
+        let sni = match self.options.connection_mode {
+            ConnectionMode::SSL => self.options.sni?,
+            _ => return None,
+        };
+
+        Some(["CIPSSLCSNI=", link, link_end, "\"", sni, "\""])
+    }
+
+    /// The parts of `AT+SAVETRANSLINK`, which does not take the UDP mode
+    fn translink_parts(&self) -> [&str; 9] {
+        let (extra_start, extra) = self.extra();
+
+        [
+            "SAVETRANSLINK=1,\"",
+            self.host.as_str(),
+            "\",",
+            self.port.as_str(),
+            ",\"",
+            self.options.connection_mode.as_str(),
+            "\"",
+            extra_start,
+            extra,
+        ]
+    }
+}
+
+/// Returns whether the line is the echo of a command
+/// Responses do not start with `AT+`, the version of `AT+GMR` starts with `AT `.
+fn is_echo(line: &[u8]) -> bool {
+    line.starts_with(&AT) || line.starts_with(ATE)
+}
+
+/// Strips the parts of a command from the start of a line
+fn strip_command<'a>(mut line: &'a [u8], command: &[&str]) -> Option<&'a [u8]> {
+    for part in command {
+        line = line.strip_prefix(part.as_bytes())?;
+    }
+
+    Some(line)
+}
+
+/// Creates a driver with the default buffer sizes
+pub fn esp01<S>(serial: S) -> Esp01<S, UnknownMode>
+where
+    S: Read + ReadReady + Write,
+{
+    Esp01::new(serial)
+}
+
+/// Creates a driver with the default buffer sizes that gives up waiting for
+/// a response when the timer expires
+pub fn esp01_with_timer<S, T>(serial: S, timer: T) -> Esp01<S, UnknownMode, 512, 256, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    Esp01::with_timer(serial, timer)
+}
+
+impl<S, const N: usize, const RX: usize> Esp01<S, UnknownMode, N, RX>
+where
+    S: Read + ReadReady + Write,
+{
+    /// Creates a driver with the buffer sizes of the type, e.g.
+    /// `let esp01: Esp01<_, _, 128, 64> = Esp01::new(serial);`
+    /// The response buffer has to fit the longest response, the echo of a command is
+    /// skipped and does not have to fit.
+    pub fn new(serial: S) -> Self {
+        Esp01::with_timer(serial, NoTimer)
+    }
+}
+
+impl<S, T, const N: usize, const RX: usize> Esp01<S, UnknownMode, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    /// Creates a driver with the buffer sizes of the type that gives up waiting for
+    /// a response when the timer expires
+    pub fn with_timer(serial: S, timer: T) -> Self {
+        Esp01 {
+            serial,
+            timer,
+            timeouts: Timeouts::new(),
+            input: [0; INPUT_LEN],
+            input_start: 0,
+            input_end: 0,
+            read_buf: [0; N],
+            rx: core::array::from_fn(|_| RingBuffer::new()),
+            frames: core::array::from_fn(|_| Queue::new()),
+            datagrams: 0,
+            links: 0,
+            accepted: 0,
+            scan_fields: ScanFields::ALL,
+            ssl_buffer_size: None,
+            echo: true,
+            urcs: Queue::new(),
+            _mode: PhantomData,
+        }
+    }
+
+    /// Queries the Wi-Fi mode, the access point and the open links and returns the driver
+    /// in the state the module is in, e.g. to resume after the microcontroller restarted.
+    /// The module has to accept commands, it can not be probed during transparent transmission.
+    pub fn probe(mut self) -> EResult<Probe<S, N, RX, T>, S::Error> {
+        let mode = parse::decimal(self.send_query(&["CWMODE_CUR"])?)
+            .and_then(Mode::from_code)
+            .ok_or(Error::UnexpectedResponse)?;
+
+        Ok(match mode {
+            Mode::StationMode => Probe::Station(
+                self.into_mode::<StationMode<APDisconnected>>()
+                    .probe_station()?,
+            ),
+            Mode::SoftAPMode => match self.probe_links()? {
+                true => Probe::SoftAPMultipleLinks(self.into_mode()),
+                false => Probe::SoftAP(self.into_mode()),
+            },
+            Mode::StationAndAPMode => Probe::StationAndAP(
+                self.into_mode::<StationAndAPMode<APDisconnected>>()
+                    .probe_station()?,
+            ),
+        })
+    }
+}
+
+impl<S, MODE, T, const N: usize, const RX: usize> Esp01<S, MODE, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    /// Writes bytes to the serial port
+    fn write_all(&mut self, data: &[u8]) -> EResult<(), S::Error> {
+        self.serial.write_all(data).map_err(Error::SerialWrite)
+    }
+
+    /// Reads what the serial port has ready into the input buffer, if the buffer is empty
+    /// Returns whether there are bytes in the input buffer, without blocking.
+    fn fill_input(&mut self) -> EResult<bool, S::Error> {
+        if self.input_start < self.input_end {
+            return Ok(true);
+        }
+        if !self.serial.read_ready().map_err(Error::SerialRead)? {
+            return Ok(false);
+        }
+
+        let n = self
+            .serial
+            .read(&mut self.input)
+            .map_err(Error::SerialRead)?;
+        self.input_start = 0;
+        self.input_end = n;
+
+        Ok(n > 0)
+    }
+
+    /// Takes the next byte from the input buffer
+    fn next_input(&mut self) -> Option<u8> {
+        if self.input_start < self.input_end {
+            let byte = self.input[self.input_start];
+            self.input_start += 1;
+            Some(byte)
+        } else {
+            None
+        }
+    }
+
+    /// Reads a byte from the serial port
+    /// The timeout is checked before each refill of the input buffer, so a module
+    /// that keeps sending still times out.
+    fn read_byte(&mut self) -> EResult<u8, S::Error> {
+        loop {
+            if let Some(byte) = self.next_input() {
+                return Ok(byte);
+            }
+            self.check_timeout()?;
+            self.fill_input()?;
+        }
+    }
+
+    /// Starts the timeout for a command
+    fn start_timeout(&mut self, timeout: Milliseconds) {
+        self.timer.start(timeout);
+    }
+
+    /// Fails with `Error::Timeout` if the timeout of the current command expired
+    fn check_timeout(&mut self) -> EResult<(), S::Error> {
+        match self.timer.wait() {
+            Ok(()) => Err(Error::Timeout),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Restarts the module by pulling its reset pin low
+    /// This also works in transparent transmission and when the module does not accept
+    /// commands anymore.
+    pub fn hardware_reset<P, D>(
+        self,
+        pin: &mut P,
+        delay: &mut D,
+    ) -> EResult<Esp01<S, UnknownMode, N, RX, T>, S::Error>
+    where
+        P: OutputPin,
+        D: DelayNs,
+    {
+        pin.set_low().map_err(|_| Error::ResetPin)?;
+        delay.delay_ms(RESET_PULSE_MS);
+        pin.set_high().map_err(|_| Error::ResetPin)?;
+
+        self.wait_ready()
+    }
+
+    /// Waits until the module printed `ready` after a reset and forgets the state
+    /// of the connections. What the boot loader prints before is dropped,
+    /// it is sent at a different baud rate and can not be read anyway.
+    fn wait_ready(mut self) -> EResult<Esp01<S, UnknownMode, N, RX, T>, S::Error> {
+        self.start_timeout(self.timeouts.boot_timeout());
+        // the end of the line, garbage of the boot loader can come before `ready`
+        let mut tail = [0; READY.len()];
+        let mut len = 0;
+
+        loop {
+            match self.read_byte()? {
+                LF if tail[0..len] == *READY => break,
+                LF => len = 0,
+                CR => {}
+                byte if len < tail.len() => {
+                    tail[len] = byte;
+                    len += 1;
+                }
+                byte => {
+                    tail.copy_within(1.., 0);
+                    tail[len - 1] = byte;
+                }
+            }
+        }
+        log_rx!(READY);
+
+        for rx in self.rx.iter_mut() {
+            rx.clear();
+        }
+        for frames in self.frames.iter_mut() {
+            while frames.dequeue().is_some() {}
+        }
+        self.datagrams = 0;
+        self.links = 0;
+        self.accepted = 0;
+        self.scan_fields = ScanFields::ALL;
+        self.ssl_buffer_size = None;
+        self.echo = true;
+
+        Ok(self.into_mode())
+    }
+
+    /// Returns the timeouts of the commands, to change them
+    /// The timeouts are kept when the driver changes its state.
+    pub fn timeouts_mut(&mut self) -> &mut Timeouts {
+        &mut self.timeouts
+    }
+
+    /// Changes the type level mode of the driver
+    fn into_mode<M>(self) -> Esp01<S, M, N, RX, T> {
+        Esp01 {
+            serial: self.serial,
+            timer: self.timer,
+            timeouts: self.timeouts,
+            input: self.input,
+            input_start: self.input_start,
+            input_end: self.input_end,
+            read_buf: self.read_buf,
+            rx: self.rx,
+            frames: self.frames,
+            datagrams: self.datagrams,
+            links: self.links,
+            accepted: self.accepted,
+            scan_fields: self.scan_fields,
+            ssl_buffer_size: self.ssl_buffer_size,
+            echo: self.echo,
+            urcs: self.urcs,
+            _mode: PhantomData,
+        }
+    }
+}
+
+impl<S, MODE, T, const N: usize, const RX: usize> Esp01<S, MODE, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    MODE: CommandMode,
+{
+    /// Writes line end sequence
+    fn write_line_end(&mut self) -> EResult<(), S::Error> {
+        self.write_all(&LINE_END)
+    }
+
+    /// Reads a line into the read buffer, continuing after the bytes that are already in
+    /// `read_buf[start..end]`. `+IPD` frames are moved into the receive buffer, the line
+    /// ends after the frame and is returned empty, because the payload is not followed by
+    /// a line end. Returns the end of the line, without the line end.
+    fn read_line_from(&mut self, start: usize, mut end: usize) -> EResult<usize, S::Error> {
+        loop {
+            match self.read_byte()? {
+                LF => {
+                    log_rx!(&self.read_buf[start..end]);
+                    return Ok(end);
+                }
+                CR => {}
+                other if end < self.read_buf.len() => {
+                    self.read_buf[end] = other;
+                    end += 1;
+
+                    if self.read_buf[start..end] == *IPD {
+                        self.read_ipd()?;
+                        return Ok(start);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Reads the response for a command
+    /// Empty lines are skipped and the remaining lines are separated by `\r\n`.
+    /// Fails with `Error::ResponseOverflow` if the response does not fit into the read buffer,
+    /// with `Error::Busy` if the module is still busy with the previous command
+    /// and with `Error::Timeout` if the timeout of the command expires.
+    pub fn read_response(&mut self) -> EResult<&[u8], S::Error> {
+        let reserve_start = self.read_buf.len().saturating_sub(LINE_RESERVE);
+        let mut len = 0;
+        let mut overflowed = false;
+        let mut code = None;
+
+        loop {
+            let start = if len == 0 { 0 } else { len + LINE_END.len() };
+            let fits = !overflowed && start <= reserve_start;
+            let start = if fits { start } else { reserve_start };
+            let end = self.read_line_from(start, start)?;
+            match self.handle_urc(start, end) {
+                Some(Urc::Busy) => return Err(Error::Busy),
+                Some(_) => continue,
+                None => {}
+            }
+            if let Some(error) = ErrorCode::parse(&self.read_buf[start..end]) {
+                code = Some(error);
+                continue;
+            }
+            if is_echo(&self.read_buf[start..end]) {
+                continue;
+            }
+
+            match &self.read_buf[start..end] {
+                OK if overflowed => return Err(Error::ResponseOverflow),
+                OK => return Ok(&self.read_buf[0..len]),
+                ERROR => return Err(Error::CommandError(code)),
+                FAIL => return Err(Error::CommandFailed),
+                [] => {}
+                _ if fits && end < self.read_buf.len() => {
+                    if len > 0 {
+                        self.read_buf[len..start].copy_from_slice(&LINE_END);
+                    }
+                    len = end;
+                }
+                _ => overflowed = true,
+            }
+        }
+    }
+
+    /// Queues the notification in `read_buf[start..end]` and updates the state of the links
+    /// Returns the notification if the line was one.
+    fn handle_urc(&mut self, start: usize, end: usize) -> Option<Urc> {
+        let urc = Urc::parse(&self.read_buf[start..end])?;
+
+        match urc {
+            Urc::LinkConnected(link) => {
+                self.links |= link.mask();
+                self.accepted |= link.mask();
+                self.datagrams &= !link.mask();
+            }
+            Urc::LinkClosed(link) => {
+                self.links &= !link.mask();
+                self.accepted &= !link.mask();
+            }
+            // the module restarted, its links and the SSL buffer size are gone
+            // and it echoes commands again
+            Urc::Ready => {
+                self.links = 0;
+                self.accepted = 0;
+                self.datagrams = 0;
+                self.ssl_buffer_size = None;
+                self.echo = true;
+            }
+            _ => {}
+        }
+
+        // the oldest notification is dropped if nobody polls them
+        if let Err(urc) = self.urcs.enqueue(urc) {
+            self.urcs.dequeue();
+            let _ = self.urcs.enqueue(urc);
+        }
+
+        Some(urc)
+    }
+
+    /// Returns the next notification the module sent without being asked
+    /// Reads what the module sent so far without blocking if nothing arrived.
+    pub fn poll_urc(&mut self) -> EResult<Option<Urc>, S::Error> {
+        self.poll()?;
+
+        Ok(self.urcs.dequeue())
+    }
+
+    /// Reads the response for a command line by line and passes each line to `f`,
+    /// so the response does not have to fit into the read buffer.
+    fn read_response_lines<F>(&mut self, mut f: F) -> EResult<(), S::Error>
+    where
+        F: FnMut(&[u8]),
+    {
+        let mut code = None;
+
+        loop {
+            let end = self.read_line_from(0, 0)?;
+            match self.handle_urc(0, end) {
+                Some(Urc::Busy) => return Err(Error::Busy),
+                Some(_) => continue,
+                None => {}
+            }
+            if let Some(error) = ErrorCode::parse(&self.read_buf[0..end]) {
+                code = Some(error);
+                continue;
+            }
+            if is_echo(&self.read_buf[0..end]) {
+                continue;
+            }
+
+            match &self.read_buf[0..end] {
+                OK => return Ok(()),
+                ERROR => return Err(Error::CommandError(code)),
+                FAIL => return Err(Error::CommandFailed),
+                [] => {}
+                line => f(line),
+            }
+        }
+    }
+
+    /// Reads the header and payload of an `+IPD,<len>:<data>` or
+    /// `+IPD,<id>,<len>:<data>` frame
+    fn read_ipd(&mut self) -> EResult<(), S::Error> {
+        let mut header = [0; 32];
+        let mut n = 0;
+
+        loop {
+            match self.read_byte()? {
+                b':' => break,
+                other if n < header.len() => {
+                    header[n] = other;
+                    n += 1;
+                }
+                _ => return Err(Error::InvalidFrame),
+            }
+        }
+
+        let (link, len, source) = parse::ipd_header(&header[0..n]).ok_or(Error::InvalidFrame)?;
+
+        // a datagram is kept whole or dropped, so its length has to be remembered
+        let datagram = self.datagrams & (1 << link) != 0;
+        let frame = Datagram {
+            len: len as u16,
+            source,
+        };
+        if datagram && (len > self.rx[link].space() || self.frames[link].enqueue(frame).is_err()) {
+            for _ in 0..len {
+                self.read_byte()?;
+            }
+            self.rx[link].set_overflow();
+            return Ok(());
+        }
+
+        for _ in 0..len {
+            let byte = self.read_byte()?;
+            self.rx[link].push(byte);
+        }
+        log_rx_data!(link, len);
+
+        Ok(())
+    }
+
+    /// Processes whatever the module sent without being asked, without blocking
+    /// if nothing arrived. Only `+IPD` frames and notifications are kept,
+    /// other lines are dropped.
+    fn poll(&mut self) -> EResult<(), S::Error> {
+        while self.fill_input()? {
+            match self.next_input() {
+                Some(CR) | Some(LF) | None => {}
+                Some(byte) => {
+                    self.start_timeout(self.timeouts.default_timeout());
+                    self.read_buf[0] = byte;
+                    let end = self.read_line_from(0, 1)?;
+                    self.handle_urc(0, end);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the `> ` prompt that asks for the payload of a send
+    fn read_prompt(&mut self) -> EResult<(), S::Error> {
+        self.read_passthrough_prompt()?;
+
+        self.read_byte_back(b' ').map_err(|_| Error::NoSendPrompt)
+    }
+
+    /// Waits for the `>` that starts transparent transmission
+    /// Unlike the prompt of a send, it is not followed by a space.
+    /// `+IPD` frames and notifications that arrive before the prompt are handled,
+    /// any other line fails with `Error::NoSendPrompt`.
+    fn read_passthrough_prompt(&mut self) -> EResult<(), S::Error> {
+        loop {
+            match self.read_byte()? {
+                CR | LF => {}
+                PROMPT => return Ok(()),
+                byte => {
+                    self.read_buf[0] = byte;
+                    let end = self.read_line_from(0, 1)?;
+                    match self.handle_urc(0, end) {
+                        Some(Urc::Busy) => return Err(Error::Busy),
+                        Some(_) => {}
+                        // the line was an `+IPD` frame
+                        None if end == 0 => {}
+                        None => return Err(Error::NoSendPrompt),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the outcome of a send after the payload has been written
+    fn read_send_result(&mut self, len: usize) -> EResult<(), S::Error> {
+        let mut received = None;
+        let mut code = None;
+
+        loop {
+            let end = self.read_line_from(0, 0)?;
+            // a link can close or a client connect before the module confirms the send
+            match self.handle_urc(0, end) {
+                Some(Urc::Busy) => return Err(Error::Busy),
+                Some(_) => continue,
+                None => {}
+            }
+
+            let line = &self.read_buf[0..end];
+            if line == SEND_OK {
+                break;
+            } else if line == SEND_FAIL {
+                return Err(Error::SendFailed);
+            } else if line == ERROR {
+                return Err(Error::CommandError(code));
+            } else if let Some(error) = ErrorCode::parse(line) {
+                code = Some(error);
+            } else if line.starts_with(RECV) && line.ends_with(BYTES) {
+                received = parse::decimal(&line[RECV.len()..(line.len() - BYTES.len())]);
+            }
+        }
+
+        match received {
+            Some(n) if n != len => Err(Error::SendIncomplete),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the payload of a send and waits until the module confirms it
+    fn send_data(&mut self, data: &[u8]) -> EResult<(), S::Error> {
+        self.read_prompt()?;
+        self.write_all(data)?;
+        log_tx_data!(data);
+
+        self.read_send_result(data.len())
+    }
+
+    /// Moves data received over a link into `buf`
+    /// Returns the number of bytes and the source of a datagram, if the module reported it.
+    fn receive_link(
+        &mut self,
+        link: usize,
+        buf: &mut [u8],
+    ) -> EResult<(usize, Option<SocketAddrV4>), S::Error> {
+        self.poll()?;
+        if self.rx[link].take_overflow() {
+            return Err(Error::ReceiveOverflow);
+        }
+
+        if self.datagrams & (1 << link) == 0 {
+            return Ok((self.rx[link].read(buf), None));
+        }
+
+        // the part of a datagram that does not fit into `buf` is dropped
+        let frame = match self.frames[link].dequeue() {
+            Some(frame) => frame,
+            None => return Ok((0, None)),
+        };
+        let len = frame.len as usize;
+        let n = len.min(buf.len());
+        let n = self.rx[link].read(&mut buf[0..n]);
+        self.rx[link].discard(len - n);
+
+        Ok((n, frame.source))
+    }
+
+    /// Reads a byte and checks that it is the expected byte
+    fn read_byte_back(&mut self, byte: u8) -> EResult<(), S::Error> {
+        if self.read_byte()? == byte {
+            Ok(())
+        } else {
+            Err(Error::CommandReadFail)
+        }
+    }
+
+    /// Sends a command
+    fn send_command(&mut self, command: &[&str]) -> EResult<(), S::Error> {
+        self.write_command(command, false)
+    }
+
+    /// Writes a command or a query and starts the timeout of the command, which covers
+    /// the command and reading its response, see `timeouts_mut`.
+    /// The echo of the command is skipped while the response is read, so it does not matter
+    /// whether the module echoes commands.
+    fn write_command(&mut self, command: &[&str], query: bool) -> EResult<(), S::Error> {
+        self.start_timeout(self.timeouts.command(command));
+        self.write_all(&AT)?;
+        for part in command {
+            self.write_all(part.as_bytes())?;
+        }
+        if query {
+            self.write_all(b"?")?;
+        }
+        self.write_line_end()?;
+        log_tx!(command, query);
+
+        Ok(())
+    }
+
+    /// Sends a query and reads its response
+    fn send_query(&mut self, command: &[&str]) -> EResult<&[u8], S::Error> {
+        self.write_command(command, true)?;
+
+        let response = self.read_response()?;
+        let prefix_len = response
+            .strip_prefix(b"+")
+            .and_then(|rest| strip_command(rest, command))
+            .and_then(|rest| rest.strip_prefix(b":"))
+            .map(|rest| response.len() - rest.len())
+            .ok_or(Error::UnexpectedResponse)?;
+
+        Ok(&response[prefix_len..])
+    }
+
+    /// Opens a connection, `link` is `None` in single connection mode
+    /// An SSL connection fails with `Error::SslNotConfigured` before anything is sent
+    /// if the SSL buffer was not enlarged with `set_ssl_buffer_size`.
+    fn start_link(
+        &mut self,
+        link: Option<LinkId>,
+        options: &ConnectOptions,
+    ) -> EResult<(), S::Error> {
+        if options.connection_mode == ConnectionMode::SSL && self.ssl_buffer_size.is_none() {
+            return Err(Error::SslNotConfigured);
+        }
+        // data of the previous connection on the link must not show up as data of this one
+        let index = link.map_or(0, |link| link.0 as usize);
+        self.rx[index].clear();
+        while self.frames[index].dequeue().is_some() {}
+
+        let command = ConnectCommand::new(link, options);
+        if let Some(sni) = command.sni_parts() {
+            self.send_command(&sni)?;
+            self.read_response()?;
+        }
+        self.send_command(&command.parts())?;
+        self.read_response()?;
+
+        // the `CONNECT` notification of the link cleared its bit
+        if options.connection_mode == ConnectionMode::UDP {
+            self.datagrams |= 1 << index;
+        }
+
+        Ok(())
+    }
+
+    /// Enables or disables the echo of commands (`ATE1`/`ATE0`)
+    /// Without the echo the module sends about half as much.
+    pub fn set_echo(&mut self, enable: bool) -> EResult<(), S::Error> {
+        let command = if enable { "E1" } else { "E0" };
+        self.start_timeout(self.timeouts.default_timeout());
+        self.write_all(b"AT")?;
+        self.write_all(command.as_bytes())?;
+        self.write_line_end()?;
+        log_tx_basic!(command);
+
+        self.read_response_lines(|_| {})?;
+        self.echo = enable;
+
+        Ok(())
+    }
+
+    /// Returns whether the module echoes commands
+    /// The module echoes them after a restart, `set_echo` turns the echo off.
+    pub fn echo_enabled(&self) -> bool {
+        self.echo
+    }
+
+    /// Restarts the module (`AT+RST`)
+    /// Open links are lost and the module is in the mode that is saved in flash.
+    pub fn reset(mut self) -> EResult<Esp01<S, UnknownMode, N, RX, T>, S::Error> {
+        self.send_command(&["RST"])?;
+        self.read_response()?;
+
+        self.wait_ready()
+    }
+
+    /// Restores the factory settings (`AT+RESTORE`), which restarts the module
+    pub fn factory_restore(mut self) -> EResult<Esp01<S, UnknownMode, N, RX, T>, S::Error> {
+        self.send_command(&["RESTORE"])?;
+        self.read_response()?;
+
+        self.wait_ready()
+    }
+
+    /// Resolves the IP address of a host with the DNS server of the module
+    pub fn resolve(&mut self, host: &str) -> EResult<Ipv4Addr, S::Error> {
+        self.send_command(&["CIPDOMAIN=\"", host, "\""])?;
+        let response = self.read_response()?;
+
+        response
+            .strip_prefix(CIPDOMAIN)
+            .and_then(parse::ipv4)
+            .ok_or(Error::UnexpectedResponse)
+    }
+
+    /// Sets the DNS servers the module uses instead of the ones it got by DHCP
+    pub fn set_dns_servers(
+        &mut self,
+        primary: Ipv4Addr,
+        secondary: Option<Ipv4Addr>,
+        persist: Persist,
+    ) -> EResult<(), S::Error> {
+        let primary = IpString::new(primary);
+        let secondary = secondary.map(IpString::new);
+        let (secondary_start, secondary, secondary_end) = match &secondary {
+            Some(secondary) => (",\"", secondary.as_str(), "\""),
+            None => ("", "", ""),
+        };
+        self.send_command(&[
+            "CIPDNS",
+            persist.as_str(),
+            "1,\"",
+            primary.as_str(),
+            "\"",
+            secondary_start,
+            secondary,
+            secondary_end,
+        ])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Goes back to the DNS servers the module got by DHCP
+    pub fn reset_dns_servers(&mut self, persist: Persist) -> EResult<(), S::Error> {
+        self.send_command(&["CIPDNS", persist.as_str(), "0"])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Gets ESP01 version information
+    pub fn get_version(&mut self) -> EResult<&[u8], S::Error> {
+        self.send_command(&["GMR"])?;
+        self.read_response()
+    }
+
+    /// Configures the UART of the module
+    /// The module confirms with the old settings and switches afterwards, the serial port
+    /// has to be changed to the new settings before the next command, see `serial_mut`.
+    pub fn configure_uart(
+        &mut self,
+        config: UartConfig,
+        persist: Persist,
+    ) -> EResult<(), S::Error> {
+        let baud_rate = Decimal::new(config.baud_rate as usize);
+        self.send_command(&[
+            "UART",
+            persist.as_str(),
+            baud_rate.as_str(),
+            ",",
+            config.data_bits.as_str(),
+            ",",
+            config.stop_bits.as_str(),
+            ",",
+            config.parity.as_str(),
+            ",",
+            config.flow_control.as_str(),
+        ])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Returns the serial port, e.g. to change its baud rate after `configure_uart`
+    pub fn serial_mut(&mut self) -> &mut S {
+        &mut self.serial
+    }
+
+    /// Sets the Wi-Fi mode
+    pub fn set_mode<M: WifiMode>(
+        mut self,
+        mode: M,
+        persist: Persist,
+    ) -> EResult<Esp01<S, M::State, N, RX, T>, S::Error> {
+        self.send_command(&["CWMODE", persist.as_str(), mode.mode().as_str()])?;
+        self.read_response()?;
+
+        Ok(self.into_mode())
+    }
+
+    /// Sets the MAC address for the station
+    pub fn set_station_mac(&mut self, mac: &str, persist: Persist) -> EResult<&[u8], S::Error> {
+        self.send_command(&["CIPSTAMAC", persist.as_str(), "\"", mac, "\""])?;
+        self.read_response()
+    }
+
+    /// Gets the MAC address for the station
+    pub fn get_station_mac(&mut self, query_mode: QueryMode) -> EResult<&[u8], S::Error> {
+        let r = self.send_query(&["CIPSTAMAC", query_mode.as_str()])?;
+        Ok(&r[1..(r.len() - 1)])
+    }
+
+    /// Gets the status of the connection and its links (`AT+CIPSTATUS`)
+    /// The driver takes over which links are open, so `check_link` and `is_connected`
+    /// are in sync with the module again, e.g. after it restarted.
+    pub fn status(&mut self) -> EResult<Status, S::Error> {
+        let mut status = None;
+        let mut links = [None; MAX_LINKS];
+
+        self.send_command(&["CIPSTATUS"])?;
+        self.read_response_lines(|line| {
+            if let Some(code) = line.strip_prefix(b"STATUS:") {
+                status = parse::decimal(code).map(ConnectionStatus::from_code);
+            } else if let Some(link) = LinkStatus::parse(line) {
+                links[link.link.0 as usize] = Some(link);
+            }
+        })?;
+        let status = status.ok_or(Error::UnexpectedResponse)?;
+
+        self.links = links
+            .iter()
+            .flatten()
+            .fold(0, |mask, link| mask | link.link.mask());
+        self.datagrams = links
+            .iter()
+            .flatten()
+            .filter(|link| link.connection_mode == ConnectionMode::UDP)
+            .fold(0, |mask, link| mask | link.link.mask());
+        self.accepted &= self.links;
+
+        Ok(Status { status, links })
+    }
+
+    /// Sets the size of the SSL buffer in bytes (2048-4096)
+    /// The default buffer is too small for the handshake with most servers,
+    /// so SSL connections can only be opened once this was set.
+    pub fn set_ssl_buffer_size(&mut self, size: u16) -> EResult<(), S::Error> {
+        if !SSL_BUFFER_SIZE.contains(&size) {
+            return Err(Error::InvalidSslBufferSize);
+        }
+
+        let param = Decimal::new(size as usize);
+        self.send_command(&["CIPSSLSIZE=", param.as_str()])?;
+        self.read_response()?;
+        self.ssl_buffer_size = Some(size);
+
+        Ok(())
+    }
+
+    /// Enables or disables reporting where received data came from (`AT+CIPDINFO`)
+    /// Datagrams read with `receive_from` then come with their source.
+    pub fn set_ipd_info(&mut self, enable: bool) -> EResult<(), S::Error> {
+        let command = if enable { "CIPDINFO=1" } else { "CIPDINFO=0" };
+        self.send_command(&[command])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Sets which certificates SSL connections check
+    pub fn set_ssl_auth(&mut self, auth: SslAuth) -> EResult<(), S::Error> {
+        self.send_command(&["CIPSSLCCONF=", auth.as_str()])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Queries whether multi-connection mode is enabled and takes over which links are open
+    fn probe_links(&mut self) -> EResult<bool, S::Error> {
+        let multiple_links = match self.send_query(&["CIPMUX"])? {
+            b"0" => false,
+            b"1" => true,
+            _ => return Err(Error::UnexpectedResponse),
+        };
+        self.status()?;
+
+        Ok(multiple_links)
+    }
+}
+
+impl<S, AP, SAP, T, const N: usize, const RX: usize> Esp01<S, StationMode<AP, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    StationMode<AP, SAP>: CommandMode,
+{
+    /// Sets which fields a scan reports and whether the access points are sorted
+    /// by signal strength, strongest first
+    pub fn set_scan_options(
+        &mut self,
+        sort_by_rssi: bool,
+        fields: ScanFields,
+    ) -> EResult<(), S::Error> {
+        let sort = match sort_by_rssi {
+            true => "1",
+            false => "0",
+        };
+        let mask = Decimal::new(fields.0 as usize);
+
+        self.send_command(&["CWLAPOPT=", sort, ",", mask.as_str()])?;
+        self.read_response()?;
+        self.scan_fields = fields;
+
+        Ok(())
+    }
+
+    /// Scans for access points
+    /// Access points that do not fit into the vector are left out.
+    pub fn scan_access_points<LEN>(&mut self) -> EResult<Vec<AccessPoint, LEN>, S::Error>
+    where
+        LEN: ArrayLength<AccessPoint>,
+    {
+        let fields = self.scan_fields;
+        let mut access_points = Vec::new();
+
+        self.send_command(&["CWLAP"])?;
+        self.read_response_lines(|line| {
+            if let Some(access_point) = AccessPoint::parse(line, fields) {
+                let _ = access_points.push(access_point);
+            }
+        })?;
+
+        Ok(access_points)
+    }
+
+    /// Gets the IP address of the station (`AT+CIFSR`)
+    pub fn get_local_ip(&mut self) -> EResult<Ipv4Addr, S::Error> {
+        let mut ip = None;
+
+        self.send_command(&["CIFSR"])?;
+        self.read_response_lines(|line| {
+            if let Some(address) = line.strip_prefix(CIFSR_STAIP) {
+                ip = parse::ipv4(address);
+            }
+        })?;
+
+        ip.ok_or(Error::UnexpectedResponse)
+    }
+
+    /// Gets the IP address, gateway and netmask of the station
+    pub fn get_ip_config(&mut self, query_mode: QueryMode) -> EResult<IpConfig, S::Error> {
+        let command = ["CIPSTA", query_mode.as_str()];
+        let mut ip = None;
+        let mut gateway = None;
+        let mut netmask = None;
+
+        self.write_command(&command, true)?;
+        self.read_response_lines(|line| {
+            let param = line
+                .strip_prefix(b"+")
+                .and_then(|rest| strip_command(rest, &command))
+                .and_then(|rest| rest.strip_prefix(b":"));
+            if let Some(param) = param {
+                if let Some(address) = param.strip_prefix(b"ip:") {
+                    ip = parse::ipv4(address);
+                } else if let Some(address) = param.strip_prefix(b"gateway:") {
+                    gateway = parse::ipv4(address);
+                } else if let Some(address) = param.strip_prefix(b"netmask:") {
+                    netmask = parse::ipv4(address);
+                }
+            }
+        })?;
+
+        match (ip, gateway, netmask) {
+            (Some(ip), Some(gateway), Some(netmask)) => Ok(IpConfig {
+                ip,
+                gateway,
+                netmask,
+            }),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// Sets a static IP address for the station, this disables DHCP
+    pub fn set_static_ip(
+        &mut self,
+        ip: Ipv4Addr,
+        gateway: Ipv4Addr,
+        netmask: Ipv4Addr,
+        persist: Persist,
+    ) -> EResult<(), S::Error> {
+        let ip = IpString::new(ip);
+        let gateway = IpString::new(gateway);
+        let netmask = IpString::new(netmask);
+        self.send_command(&[
+            "CIPSTA",
+            persist.as_str(),
+            "\"",
+            ip.as_str(),
+            "\",\"",
+            gateway.as_str(),
+            "\",\"",
+            netmask.as_str(),
+            "\"",
+        ])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Enables or disables DHCP for the station
+    pub fn set_dhcp(&mut self, enable: bool, persist: Persist) -> EResult<(), S::Error> {
+        let enable = if enable { "1" } else { "0" };
+        self.send_command(&["CWDHCP", persist.as_str(), "1,", enable])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Saves a connection the module opens in transparent transmission on power up,
+    /// `None` clears it. This configuration is saved in flash.
+    pub fn save_passthrough_link(
+        &mut self,
+        options: Option<&ConnectOptions>,
+    ) -> EResult<(), S::Error> {
+        match options {
+            Some(options) => {
+                let command = ConnectCommand::new(None, options);
+                self.send_command(&command.translink_parts())?;
+            }
+            None => self.send_command(&["SAVETRANSLINK=0"])?,
+        }
+        self.read_response()?;
+
+        Ok(())
+    }
+}
+
+impl<S, SAP, T, const N: usize, const RX: usize>
+    Esp01<S, StationMode<APDisconnected, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    /// Finds out whether the station is connected and which links are open
+    fn probe_station(mut self) -> EResult<StationProbe<S, SAP, N, RX, T>, S::Error> {
+        let command = ["CWJAP_CUR"];
+        self.write_command(&command, true)?;
+        // the module answers `No AP` if it is not connected
+        let joined = self.read_response()?.starts_with(b"+CWJAP_CUR:");
+        let multiple_links = self.probe_links()?;
+
+        Ok(if !joined && multiple_links {
+            StationProbe::DisconnectedMultipleLinks(self.into_mode())
+        } else if !joined {
+            StationProbe::Disconnected(self)
+        } else if multiple_links {
+            StationProbe::MultipleLinks(self.into_mode())
+        } else if self.links & LinkId(0).mask() != 0 {
+            StationProbe::Linked(self.into_mode())
+        } else {
+            StationProbe::Joined(self.into_mode())
+        })
+    }
+}
+
+impl<S, L, SAP, T, const N: usize, const RX: usize>
+    Esp01<S, StationMode<APDisconnected<L>, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    StationMode<APDisconnected<L>, SAP>: CommandMode,
+{
+    /// Connects to an access point
+    pub fn connect_ap(
+        self,
+        ssid: &str,
+        password: &str,
+        persist: Persist,
+    ) -> EResult<Esp01<S, StationMode<APConnected<L>, SAP>, N, RX, T>, S::Error> {
+        self.connect_ap_with_options(ssid, password, &JoinOptions::new(), persist)
+    }
+
+    /// Connects to an access point with optional parameters
+    /// Fails with `Error::JoinFailed` if the module reports why it could not connect.
+    pub fn connect_ap_with_options(
+        mut self,
+        ssid: &str,
+        password: &str,
+        options: &JoinOptions,
+        persist: Persist,
+    ) -> EResult<Esp01<S, StationMode<APConnected<L>, SAP>, N, RX, T>, S::Error> {
+        // parameters can only be left out at the end, so the ones before the last
+        // given parameter are filled with their defaults
+        let last = if options.listen_interval.is_some() {
+            4
+        } else if options.reconnect_interval.is_some() {
+            3
+        } else if options.pci_en.is_some() {
+            2
+        } else if options.bssid.is_some() {
+            1
+        } else {
+            0
+        };
+        let (bssid_start, bssid, bssid_end) = match options.bssid {
+            Some(bssid) => (",\"", bssid, "\""),
+            None if last > 1 => (",", "", ""),
+            None => ("", "", ""),
+        };
+        let pci_en = match options.pci_en {
+            _ if last < 2 => "",
+            Some(true) => ",1",
+            Some(false) | None => ",0",
+        };
+        let reconnect_interval = Decimal::new(options.reconnect_interval.unwrap_or(1) as usize);
+        let (reconnect_start, reconnect) = match last {
+            3..=4 => (",", reconnect_interval.as_str()),
+            _ => ("", ""),
+        };
+        let listen_interval = Decimal::new(options.listen_interval.unwrap_or(3) as usize);
+        let (listen_start, listen) = match last {
+            4 => (",", listen_interval.as_str()),
+            _ => ("", ""),
+        };
+
+        self.send_command(&[
+            "CWJAP",
+            persist.as_str(),
+            "\"",
+            ssid,
+            "\",\"",
+            password,
+            "\"",
+            bssid_start,
+            bssid,
+            bssid_end,
+            pci_en,
+            reconnect_start,
+            reconnect,
+            listen_start,
+            listen,
+        ])?;
+
+        let mut reason = None;
+        let result = self.read_response_lines(|line| {
+            if let Some(error) = JoinError::parse(line) {
+                reason = Some(error);
+            }
+        });
+
+        // older firmware ends a failed join with `FAIL`, ESP-AT 2.x with `ERROR`
+        match (result, reason) {
+            (Err(Error::CommandFailed), Some(reason))
+            | (Err(Error::CommandError(_)), Some(reason)) => return Err(Error::JoinFailed(reason)),
+            (result, _) => result?,
+        }
+
+        Ok(self.into_mode())
+    }
+}
+
+impl<S, L, SAP, T, const N: usize, const RX: usize>
+    Esp01<S, StationMode<APConnected<L>, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    L: LinkMode,
+    StationMode<APConnected<L>, SAP>: CommandMode,
+{
+    /// Disconnects from the access point
+    /// Multi-connection mode stays enabled, a single link is closed.
+    pub fn disconnect_ap(
+        mut self,
+    ) -> EResult<Esp01<S, StationMode<APDisconnected<L::Closed>, SAP>, N, RX, T>, S::Error> {
+        self.send_command(&["CWQAP"])?;
+        self.read_response()?;
+
+        Ok(self.into_mode())
+    }
+
+    /// Enables/Disables autoconnection to the accesspoint on power up
+    /// This configuration is saved in flash.
+    pub fn autoconnect_ap(&mut self, enable: bool) -> EResult<(), S::Error> {
+        let param = match enable {
+            true => "1",
+            false => "0",
+        };
+
+        self.send_command(&["CWAUTOCONN=", param])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+}
+
+impl<S, SAP, T, const N: usize, const RX: usize>
+    Esp01<S, StationMode<APConnected<LinkDisconnected>, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    /// Connects to an endpoint
+    pub fn connect(
+        mut self,
+        options: &ConnectOptions,
+    ) -> EResult<Esp01<S, StationMode<APConnected<LinkConnected>, SAP>, N, RX, T>, S::Error> {
+        self.start_link(None, options)?;
+        self.links |= LinkId(0).mask();
+
+        Ok(self.into_mode())
+    }
+}
+
+impl<S, SAP, T, const N: usize, const RX: usize>
+    Esp01<S, StationMode<APConnected<LinkConnected>, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    /// Sends data over the connection
+    /// At most `MAX_SEND_LEN` bytes can be sent at once.
+    pub fn send(&mut self, data: &[u8]) -> EResult<(), S::Error> {
+        if data.len() > MAX_SEND_LEN {
+            return Err(Error::SendTooLarge);
+        }
+
+        let len = Decimal::new(data.len());
+        self.send_command(&["CIPSEND=", len.as_str()])?;
+        self.read_response()?;
+
+        self.send_data(data)
+    }
+
+    /// Reads data received over the connection into `buf` and returns the number of bytes read.
+    /// Returns `Ok(0)` if no data is available.
+    /// `Error::ReceiveOverflow` is returned once if data was dropped because the receive
+    /// buffer was full. The data that was kept can be read with the following calls.
+    /// Over UDP each call returns one datagram, the part that does not fit into `buf` is dropped.
+    pub fn receive(&mut self, buf: &mut [u8]) -> EResult<usize, S::Error> {
+        Ok(self.receive_link(0, buf)?.0)
+    }
+
+    /// Reads a datagram like `receive` and returns where it came from
+    /// The source is only known if it was enabled with `set_ipd_info` and the connection is UDP.
+    pub fn receive_from(
+        &mut self,
+        buf: &mut [u8],
+    ) -> EResult<(usize, Option<SocketAddrV4>), S::Error> {
+        self.receive_link(0, buf)
+    }
+
+    /// Returns whether the module has not reported the connection as closed
+    /// Notifications are only processed while commands run or by `poll_urc`.
+    pub fn is_connected(&self) -> bool {
+        self.links & LinkId(0).mask() != 0
+    }
+
+    /// Moves to the disconnected state if the module reported the connection as closed
+    pub fn check_link(
+        self,
+    ) -> LinkState<Self, Esp01<S, StationMode<APConnected<LinkDisconnected>, SAP>, N, RX, T>> {
+        if self.is_connected() {
+            LinkState::Connected(self)
+        } else {
+            LinkState::Closed(self.into_mode())
+        }
+    }
+
+    /// Sets how `close` closes the connection
+    pub fn set_close_mode(&mut self, mode: CloseMode) -> EResult<(), S::Error> {
+        self.send_command(&["CIPCLOSEMODE=", mode.as_str()])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Closes the connection
+    /// Nothing is sent if the module already reported the connection as closed.
+    pub fn close(
+        mut self,
+    ) -> EResult<Esp01<S, StationMode<APConnected<LinkDisconnected>, SAP>, N, RX, T>, S::Error>
+    {
+        if self.is_connected() {
+            self.send_command(&["CIPCLOSE"])?;
+            self.read_response()?;
+        }
+        self.links &= !LinkId(0).mask();
+
+        Ok(self.into_mode())
+    }
+
+    /// Starts transparent transmission, the driver then reads and writes the data of the
+    /// connection through the `embedded-io` traits until `exit_passthrough` is called.
+    /// Data that was received before is read first.
+    pub fn start_passthrough(
+        mut self,
+    ) -> EResult<Esp01<S, StationMode<APConnected<Passthrough>, SAP>, N, RX, T>, S::Error> {
+        self.send_command(&["CIPMODE=1"])?;
+        self.read_response()?;
+
+        self.send_command(&["CIPSEND"])?;
+        self.read_response()?;
+        self.read_passthrough_prompt()?;
+        // transparent transmission does not keep the datagram boundaries
+        self.datagrams &= !LinkId(0).mask();
+
+        Ok(self.into_mode())
+    }
+}
+
+impl<S, SAP, T, const N: usize, const RX: usize>
+    Esp01<S, StationMode<APConnected<Passthrough>, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    /// Ends transparent transmission and returns to commands
+    /// `+++` has to be sent with a pause before and after it, `delay` waits for the pauses.
+    /// Data that arrives until the module stops transmitting can still be read with `receive`.
+    pub fn exit_passthrough<D: DelayNs>(
+        mut self,
+        delay: &mut D,
+    ) -> EResult<Esp01<S, StationMode<APConnected<LinkConnected>, SAP>, N, RX, T>, S::Error> {
+        self.serial.flush().map_err(Error::SerialWrite)?;
+        delay.delay_ms(PASSTHROUGH_GUARD_MS);
+        self.write_all(PASSTHROUGH_EXIT)?;
+        self.serial.flush().map_err(Error::SerialWrite)?;
+        delay.delay_ms(PASSTHROUGH_EXIT_MS);
+
+        // the data must not be taken for the response of the next command
+        while self.fill_input()? {
+            while let Some(byte) = self.next_input() {
+                self.rx[0].push(byte);
+            }
+        }
+
+        let mut esp01: Esp01<S, StationMode<APConnected<LinkConnected>, SAP>, N, RX, T> =
+            self.into_mode();
+        esp01.send_command(&["CIPMODE=0"])?;
+        esp01.read_response()?;
+
+        Ok(esp01)
+    }
+}
+
+impl<S, SAP, T, const N: usize, const RX: usize> ErrorType
+    for Esp01<S, StationMode<APConnected<Passthrough>, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+{
+    type Error = S::Error;
+}
+
+/// Reads the data of the connection, blocks until some arrived
+impl<S, SAP, T, const N: usize, const RX: usize> Read
+    for Esp01<S, StationMode<APConnected<Passthrough>, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, S::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let n = self.rx[0].read(buf);
+            if n > 0 {
+                return Ok(n);
+            }
+
+            if self.input_start < self.input_end {
+                let n = buf.len().min(self.input_end - self.input_start);
+                buf[0..n].copy_from_slice(&self.input[self.input_start..(self.input_start + n)]);
+                self.input_start += n;
+                return Ok(n);
+            }
+
+            let n = self.serial.read(buf)?;
+            if n > 0 {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+impl<S, SAP, T, const N: usize, const RX: usize> ReadReady
+    for Esp01<S, StationMode<APConnected<Passthrough>, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+{
+    fn read_ready(&mut self) -> Result<bool, S::Error> {
+        if !self.rx[0].is_empty() || self.input_start < self.input_end {
+            return Ok(true);
+        }
+
+        self.serial.read_ready()
+    }
+}
+
+/// Writes data to the connection
+/// The module sends a packet when it has 2048 bytes or after 20 ms without data.
+/// A packet that only contains `+++` ends the transmission, use `exit_passthrough` for that.
+impl<S, SAP, T, const N: usize, const RX: usize> Write
+    for Esp01<S, StationMode<APConnected<Passthrough>, SAP>, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, S::Error> {
+        self.serial.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), S::Error> {
+        self.serial.flush()
+    }
+}
+
+impl<S, MODE, T, const N: usize, const RX: usize> Esp01<S, MODE, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    MODE: MultipleLinksDisabled + CommandMode,
+{
+    /// Enables multi-connection mode
+    pub fn enable_multiple_connections(
+        mut self,
+    ) -> EResult<Esp01<S, MODE::Enabled, N, RX, T>, S::Error> {
+        self.send_command(&["CIPMUX=1"])?;
+        self.read_response()?;
+
+        Ok(self.into_mode())
+    }
+}
+
+impl<S, MODE, T, const N: usize, const RX: usize> Esp01<S, MODE, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    MODE: MultipleLinksEnabled + CommandMode,
+{
+    /// Disables multi-connection mode
+    /// This fails while links are still open.
+    pub fn disable_multiple_connections(
+        mut self,
+    ) -> EResult<Esp01<S, MODE::Disabled, N, RX, T>, S::Error> {
+        self.send_command(&["CIPMUX=0"])?;
+        self.read_response()?;
+
+        Ok(self.into_mode())
+    }
+
+    /// Connects to an endpoint on the first free link
+    pub fn connect(&mut self, options: &ConnectOptions) -> EResult<LinkId, S::Error> {
+        let link = (0..MAX_LINKS as u8)
+            .filter_map(LinkId::new)
+            .find(|link| self.links & link.mask() == 0)
+            .ok_or(Error::NoFreeLink)?;
+
+        self.connect_link(link, options)?;
+
+        Ok(link)
+    }
+
+    /// Connects to an endpoint on the given link
+    fn connect_link(&mut self, link: LinkId, options: &ConnectOptions) -> EResult<(), S::Error> {
+        self.start_link(Some(link), options)?;
+        self.links |= link.mask();
+        self.accepted &= !link.mask();
+
+        Ok(())
+    }
+
+    /// Returns whether the link is open
+    pub fn is_connected(&self, link: LinkId) -> bool {
+        self.links & link.mask() != 0
+    }
+
+    /// Sends data over the link
+    /// At most `MAX_SEND_LEN` bytes can be sent at once.
+    pub fn send(&mut self, link: LinkId, data: &[u8]) -> EResult<(), S::Error> {
+        if data.len() > MAX_SEND_LEN {
+            return Err(Error::SendTooLarge);
+        }
+
+        let len = Decimal::new(data.len());
+        self.send_command(&["CIPSEND=", link.as_str(), ",", len.as_str()])?;
+        self.read_response()?;
+
+        self.send_data(data)
+    }
+
+    /// Reads data received over the link, see `receive` of a single connection.
+    pub fn receive(&mut self, link: LinkId, buf: &mut [u8]) -> EResult<usize, S::Error> {
+        Ok(self.receive_link(link.0 as usize, buf)?.0)
+    }
+
+    /// Reads a datagram received over the link, see `receive_from` of a single connection.
+    pub fn receive_from(
+        &mut self,
+        link: LinkId,
+        buf: &mut [u8],
+    ) -> EResult<(usize, Option<SocketAddrV4>), S::Error> {
+        self.receive_link(link.0 as usize, buf)
+    }
+
+    /// Closes the link
+    pub fn close(&mut self, link: LinkId) -> EResult<(), S::Error> {
+        self.send_command(&["CIPCLOSE=", link.as_str()])?;
+        self.read_response()?;
+        self.links &= !link.mask();
+        self.accepted &= !link.mask();
+
+        Ok(())
+    }
+
+    /// Sets how `close` closes the link
+    pub fn set_close_mode(&mut self, link: LinkId, mode: CloseMode) -> EResult<(), S::Error> {
+        self.send_command(&["CIPCLOSEMODE=", link.as_str(), ",", mode.as_str()])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Sets the maximum number of clients the server accepts (1-5)
+    /// This has to be set before the server is started.
+    pub fn set_server_max_connections(&mut self, max: u8) -> EResult<(), S::Error> {
+        let max = Decimal::new(max as usize);
+        self.send_command(&["CIPSERVERMAXCONN=", max.as_str()])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Starts a TCP server listening on the port
+    pub fn start_server(&mut self, port: u16) -> EResult<(), S::Error> {
+        let port = Decimal::new(port as usize);
+        self.send_command(&["CIPSERVER=1,", port.as_str()])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Stops the TCP server
+    pub fn stop_server(&mut self) -> EResult<(), S::Error> {
+        self.send_command(&["CIPSERVER=0"])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Sets the time in seconds after which the server closes idle client connections (0-7200)
+    /// A timeout of 0 never closes them.
+    pub fn set_server_timeout(&mut self, seconds: u16) -> EResult<(), S::Error> {
+        let seconds = Decimal::new(seconds as usize);
+        self.send_command(&["CIPSTO=", seconds.as_str()])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Returns the link of the next client that connected to the server
+    pub fn accept(&mut self) -> nb::Result<LinkId, Error<S::Error>> {
+        self.poll()?;
+
+        let link = (0..MAX_LINKS as u8)
+            .filter_map(LinkId::new)
+            .find(|link| self.accepted & link.mask() != 0)
+            .ok_or(nb::Error::WouldBlock)?;
+        self.accepted &= !link.mask();
+
+        Ok(link)
+    }
+}
+
+impl<S, MODE, T, const N: usize, const RX: usize> Esp01<S, MODE, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+    MODE: SoftAPEnabled + CommandMode,
+{
+    /// Configures the SoftAP
+    /// `channel` is the Wi-Fi channel (1-13), `max_connections` the number of stations
+    /// that can connect (1-4).
+    #[allow(clippy::too_many_arguments)]
+    pub fn configure_ap(
+        &mut self,
+        ssid: &str,
+        password: &str,
+        channel: u8,
+        encryption: ApEncryption,
+        max_connections: u8,
+        hidden: bool,
+        persist: Persist,
+    ) -> EResult<(), S::Error> {
+        let channel = Decimal::new(channel as usize);
+        let max_connections = Decimal::new(max_connections as usize);
+        let hidden = match hidden {
+            true => "1",
+            false => "0",
+        };
+
+        self.send_command(&[
+            "CWSAP",
+            persist.as_str(),
+            "\"",
+            ssid,
+            "\",\"",
+            password,
+            "\",",
+            channel.as_str(),
+            ",",
+            encryption.as_str(),
+            ",",
+            max_connections.as_str(),
+            ",",
+            hidden,
+        ])?;
+        self.read_response()?;
+
+        Ok(())
+    }
+
+    /// Lists the stations connected to the SoftAP
+    /// Stations that do not fit into the vector are left out.
+    pub fn get_connected_stations<LEN>(&mut self) -> EResult<Vec<ConnectedStation, LEN>, S::Error>
+    where
+        LEN: ArrayLength<ConnectedStation>,
+    {
+        self.send_command(&["CWLIF"])?;
+        let response = self.read_response()?;
+
+        let mut stations = Vec::new();
+        for line in response.split(|b| *b == LF) {
+            let line = line.strip_suffix(&[CR]).unwrap_or(line);
+            let line = line.strip_prefix(b"+CWLIF:").unwrap_or(line);
+            let mut params = parse::params(line);
+            let ip = params.next().and_then(parse::ipv4);
+            let mac = params.next().and_then(parse::mac);
+            if let (Some(ip), Some(mac)) = (ip, mac) {
+                if stations.push(ConnectedStation { ip, mac }).is_err() {
+                    break;
+                }
+            }
+        }
+
+        Ok(stations)
+    }
+
+    /// Sets the IP address of the SoftAP
+    pub fn set_ap_ip(&mut self, ip: Ipv4Addr, persist: Persist) -> EResult<(), S::Error> {
+        let ip = IpString::new(ip);
+        self.send_command(&["CIPAP", persist.as_str(), "\"", ip.as_str(), "\""])?;
+        self.read_response()?;
 
         Ok(())
     }
@@ -0,0 +1,58 @@
+//! Logging of the AT exchanges with the module, enabled by the `defmt` feature.
+
+/// Formats a command the way it is sent to the module
+#[cfg(feature = "defmt")]
+pub(crate) struct Command<'a>(pub &'a [&'a str], pub bool);
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Command<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "AT+");
+        for part in self.0 {
+            defmt::write!(f, "{=str}", part);
+        }
+        if self.1 {
+            defmt::write!(f, "?");
+        }
+    }
+}
+
+/// Logs a command or query that is sent to the module
+macro_rules! log_tx {
+    ($command:expr, $query:expr) => {
+        #[cfg(feature = "defmt")]
+        defmt::debug!("esp01 > {}", $crate::log::Command($command, $query));
+    };
+}
+
+/// Logs a basic command, which is sent without the `+`
+macro_rules! log_tx_basic {
+    ($command:expr) => {
+        #[cfg(feature = "defmt")]
+        defmt::debug!("esp01 > AT{=str}", $command);
+    };
+}
+
+/// Logs the payload that is sent over a link
+macro_rules! log_tx_data {
+    ($data:expr) => {
+        #[cfg(feature = "defmt")]
+        defmt::debug!("esp01 > {=usize} bytes", $data.len());
+    };
+}
+
+/// Logs a line received from the module
+macro_rules! log_rx {
+    ($line:expr) => {
+        #[cfg(feature = "defmt")]
+        defmt::debug!("esp01 < {=[u8]:a}", $line);
+    };
+}
+
+/// Logs data received over a link
+macro_rules! log_rx_data {
+    ($link:expr, $len:expr) => {
+        #[cfg(feature = "defmt")]
+        defmt::debug!("esp01 < +IPD {=usize} bytes on link {=usize}", $len, $link);
+    };
+}
@@ -0,0 +1,254 @@
+//! `embedded-nal` network stack on top of the driver
+//! Sockets map to the links of the multi-connection mode, so generic networking crates
+//! can use the module without depending on the typestate API of the driver.
+
+use core::net::{IpAddr, SocketAddr, SocketAddrV4};
+
+use embedded_hal::timer::CountDown;
+use embedded_io::{Read, ReadReady, Write};
+use embedded_nal::nb;
+use embedded_nal::{AddrType, Dns, TcpClientStack, TcpError, TcpErrorKind, UdpClientStack};
+
+use crate::errors::{EResult, Error};
+use crate::timer::Milliseconds;
+use crate::{
+    APConnected, ConnectOptions, ConnectionMode, Esp01, LinkId, MultipleLinks, StationMode,
+    MAX_LINKS, MAX_SEND_LEN,
+};
+
+/// A TCP socket, backed by a link of the module
+#[derive(Debug, Eq, PartialEq)]
+pub struct TcpSocket {
+    link: LinkId,
+}
+
+/// A UDP socket, backed by a link of the module
+#[derive(Debug, Eq, PartialEq)]
+pub struct UdpSocket {
+    link: LinkId,
+    remote: Option<SocketAddr>,
+}
+
+/// A network stack that owns a driver in multi-connection mode
+pub struct Stack<S, SAP, const N: usize, const RX: usize, T> {
+    esp: Esp01<S, StationMode<APConnected<MultipleLinks>, SAP>, N, RX, T>,
+    /// Links that are handed out as sockets
+    sockets: u8,
+}
+
+impl<S, SAP, T, const N: usize, const RX: usize> Stack<S, SAP, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    pub fn new(esp: Esp01<S, StationMode<APConnected<MultipleLinks>, SAP>, N, RX, T>) -> Self {
+        Stack { esp, sockets: 0 }
+    }
+
+    /// Returns the driver, links that are still open stay open
+    pub fn release(self) -> Esp01<S, StationMode<APConnected<MultipleLinks>, SAP>, N, RX, T> {
+        self.esp
+    }
+
+    /// Reserves a link that is neither handed out as a socket nor in use by the server
+    fn allocate(&mut self) -> EResult<LinkId, S::Error> {
+        let link = (0..MAX_LINKS as u8)
+            .filter_map(LinkId::new)
+            .find(|link| (self.sockets | self.esp.links) & link.mask() == 0)
+            .ok_or(Error::NoFreeLink)?;
+        self.sockets |= link.mask();
+
+        Ok(link)
+    }
+
+    /// Closes the link if it is still open and frees it
+    fn release_link(&mut self, link: LinkId) -> EResult<(), S::Error> {
+        self.sockets &= !link.mask();
+        if self.esp.is_connected(link) {
+            self.esp.close(link)?;
+        }
+
+        Ok(())
+    }
+
+    /// Opens the link to the remote address
+    fn connect_link(
+        &mut self,
+        link: LinkId,
+        connection_mode: ConnectionMode,
+        remote: SocketAddr,
+    ) -> EResult<(), S::Error> {
+        let ip = match remote.ip() {
+            IpAddr::V4(ip) => ip,
+            IpAddr::V6(_) => return Err(Error::Unsupported),
+        };
+        let options = ConnectOptions::new(connection_mode, ip, remote.port());
+
+        self.esp.connect_link(link, &options)
+    }
+
+    /// Sends as much of `buffer` as fits into a single send and returns the number of bytes sent
+    fn send_link(&mut self, link: LinkId, buffer: &[u8]) -> EResult<usize, S::Error> {
+        if !self.esp.is_connected(link) {
+            return Err(Error::LinkClosed);
+        }
+
+        let len = buffer.len().min(MAX_SEND_LEN);
+        self.esp.send(link, &buffer[0..len])?;
+
+        Ok(len)
+    }
+
+    /// Reads buffered data of the link, blocks in the sense of `nb` if there is none
+    /// Returns the number of bytes and the source of a datagram, if the module reported it.
+    fn receive_link(
+        &mut self,
+        link: LinkId,
+        buffer: &mut [u8],
+    ) -> nb::Result<(usize, Option<SocketAddrV4>), Error<S::Error>> {
+        match self.esp.receive_from(link, buffer)? {
+            (0, _) if self.esp.is_connected(link) => Err(nb::Error::WouldBlock),
+            (0, _) => Err(nb::Error::Other(Error::LinkClosed)),
+            received => Ok(received),
+        }
+    }
+}
+
+impl<E: core::fmt::Debug> TcpError for Error<E> {
+    fn kind(&self) -> TcpErrorKind {
+        match self {
+            Error::LinkClosed => TcpErrorKind::PipeClosed,
+            _ => TcpErrorKind::Other,
+        }
+    }
+}
+
+impl<S, SAP, T, const N: usize, const RX: usize> TcpClientStack for Stack<S, SAP, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    type TcpSocket = TcpSocket;
+    type Error = Error<S::Error>;
+
+    fn socket(&mut self) -> Result<TcpSocket, Error<S::Error>> {
+        Ok(TcpSocket {
+            link: self.allocate()?,
+        })
+    }
+
+    fn connect(
+        &mut self,
+        socket: &mut TcpSocket,
+        remote: SocketAddr,
+    ) -> nb::Result<(), Error<S::Error>> {
+        Ok(self.connect_link(socket.link, ConnectionMode::TCP, remote)?)
+    }
+
+    fn send(
+        &mut self,
+        socket: &mut TcpSocket,
+        buffer: &[u8],
+    ) -> nb::Result<usize, Error<S::Error>> {
+        Ok(self.send_link(socket.link, buffer)?)
+    }
+
+    fn receive(
+        &mut self,
+        socket: &mut TcpSocket,
+        buffer: &mut [u8],
+    ) -> nb::Result<usize, Error<S::Error>> {
+        Ok(self.receive_link(socket.link, buffer)?.0)
+    }
+
+    fn close(&mut self, socket: TcpSocket) -> Result<(), Error<S::Error>> {
+        self.release_link(socket.link)
+    }
+}
+
+impl<S, SAP, T, const N: usize, const RX: usize> UdpClientStack for Stack<S, SAP, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    type UdpSocket = UdpSocket;
+    type Error = Error<S::Error>;
+
+    fn socket(&mut self) -> Result<UdpSocket, Error<S::Error>> {
+        Ok(UdpSocket {
+            link: self.allocate()?,
+            remote: None,
+        })
+    }
+
+    fn connect(
+        &mut self,
+        socket: &mut UdpSocket,
+        remote: SocketAddr,
+    ) -> Result<(), Error<S::Error>> {
+        self.connect_link(socket.link, ConnectionMode::UDP, remote)?;
+        socket.remote = Some(remote);
+
+        Ok(())
+    }
+
+    fn send(&mut self, socket: &mut UdpSocket, buffer: &[u8]) -> nb::Result<(), Error<S::Error>> {
+        // a datagram has to be sent at once
+        if buffer.len() > MAX_SEND_LEN {
+            return Err(nb::Error::Other(Error::SendTooLarge));
+        }
+        self.send_link(socket.link, buffer)?;
+
+        Ok(())
+    }
+
+    /// The module only reports where a datagram came from after `Esp01::set_ipd_info`,
+    /// else the address the socket is connected to is returned.
+    /// Each call returns one datagram, the part that does not fit into `buffer` is dropped.
+    fn receive(
+        &mut self,
+        socket: &mut UdpSocket,
+        buffer: &mut [u8],
+    ) -> nb::Result<(usize, SocketAddr), Error<S::Error>> {
+        let remote = socket.remote.ok_or(nb::Error::Other(Error::LinkClosed))?;
+        let (n, source) = self.receive_link(socket.link, buffer)?;
+
+        Ok((n, source.map(SocketAddr::V4).unwrap_or(remote)))
+    }
+
+    fn close(&mut self, socket: UdpSocket) -> Result<(), Error<S::Error>> {
+        self.release_link(socket.link)
+    }
+}
+
+impl<S, SAP, T, const N: usize, const RX: usize> Dns for Stack<S, SAP, N, RX, T>
+where
+    S: Read + ReadReady + Write,
+    T: CountDown,
+    T::Time: From<Milliseconds>,
+{
+    type Error = Error<S::Error>;
+
+    fn get_host_by_name(
+        &mut self,
+        hostname: &str,
+        addr_type: AddrType,
+    ) -> nb::Result<IpAddr, Error<S::Error>> {
+        if addr_type == AddrType::IPv6 {
+            return Err(nb::Error::Other(Error::Unsupported));
+        }
+        Ok(IpAddr::V4(self.esp.resolve(hostname)?))
+    }
+
+    /// The module can not look up host names by address
+    fn get_host_by_address(
+        &mut self,
+        _addr: IpAddr,
+        _result: &mut [u8],
+    ) -> nb::Result<usize, Error<S::Error>> {
+        Err(nb::Error::Other(Error::Unsupported))
+    }
+}
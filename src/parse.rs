@@ -0,0 +1,251 @@
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use heapless::{ArrayLength, String, Vec};
+
+/// Parses an unsigned decimal number
+pub fn decimal(digits: &[u8]) -> Option<usize> {
+    if digits.is_empty() {
+        return None;
+    }
+    digits.iter().try_fold(0usize, |n, d| match d {
+        b'0'..=b'9' => n.checked_mul(10)?.checked_add((d - b'0') as usize),
+        _ => None,
+    })
+}
+
+/// Parses a signed decimal number
+pub fn signed(digits: &[u8]) -> Option<isize> {
+    match digits.strip_prefix(b"-") {
+        Some(digits) => Some(-(decimal(digits)? as isize)),
+        None => Some(decimal(digits)? as isize),
+    }
+}
+
+/// Removes the quotes around a string parameter
+pub fn unquote(param: &[u8]) -> Option<&[u8]> {
+    param.strip_prefix(b"\"")?.strip_suffix(b"\"")
+}
+
+/// Removes the quotes around a string parameter and the backslashes the firmware puts
+/// before `"`, `,` and `\`. Returns `None` if the string is not UTF-8 or does not fit.
+pub fn unescape<LEN: ArrayLength<u8>>(param: &[u8]) -> Option<String<LEN>> {
+    let mut bytes: Vec<u8, LEN> = Vec::new();
+    let mut escaped = false;
+    for b in unquote(param)? {
+        match b {
+            b'\\' if !escaped => escaped = true,
+            _ => {
+                escaped = false;
+                bytes.push(*b).ok()?;
+            }
+        }
+    }
+
+    let mut string = String::new();
+    string.push_str(core::str::from_utf8(&bytes).ok()?).ok()?;
+    Some(string)
+}
+
+/// Parses an IPv4 address like `192.168.4.1`, with or without quotes
+pub fn ipv4(param: &[u8]) -> Option<Ipv4Addr> {
+    let mut octets = [0; 4];
+    let mut parts = unquote(param).unwrap_or(param).split(|b| *b == b'.');
+    for octet in octets.iter_mut() {
+        *octet = decimal(parts.next()?).filter(|n| *n <= 255)? as u8;
+    }
+
+    match parts.next() {
+        None => Some(Ipv4Addr::from(octets)),
+        Some(_) => None,
+    }
+}
+
+/// Parses a MAC address like `aa:bb:cc:dd:ee:ff`, with or without quotes
+pub fn mac(param: &[u8]) -> Option<[u8; 6]> {
+    let mut mac = [0; 6];
+    let mut parts = unquote(param).unwrap_or(param).split(|b| *b == b':');
+    for byte in mac.iter_mut() {
+        match parts.next()? {
+            [high, low] => *byte = hex_digit(*high)? << 4 | hex_digit(*low)?,
+            _ => return None,
+        }
+    }
+
+    match parts.next() {
+        None => Some(mac),
+        Some(_) => None,
+    }
+}
+
+/// Parses a hexadecimal number without prefix
+pub fn hex(digits: &[u8]) -> Option<u32> {
+    if digits.is_empty() || digits.len() > 8 {
+        return None;
+    }
+    digits
+        .iter()
+        .try_fold(0u32, |n, d| Some(n << 4 | hex_digit(*d)? as u32))
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Parses the header of an `+IPD,[<id>,]<len>[,<ip>,<port>]:` frame, the part between
+/// `+IPD,` and `:`. The source is only sent after `AT+CIPDINFO=1`.
+/// Returns the link, the length of the data and the source.
+pub fn ipd_header(header: &[u8]) -> Option<(usize, usize, Option<SocketAddrV4>)> {
+    let mut fields = header.split(|b| *b == b',');
+    let first = fields.next().and_then(decimal);
+    let fields = (fields.next(), fields.next(), fields.next(), fields.next());
+    let (link, len, source) = match fields {
+        (None, None, None, None) => (0, first?, None),
+        (Some(len), None, None, None) => (first?, decimal(len)?, None),
+        (Some(ip), Some(port), None, None) => (0, first?, Some((ip, port))),
+        (Some(len), Some(ip), Some(port), None) => (first?, decimal(len)?, Some((ip, port))),
+        _ => return None,
+    };
+    let source = match source {
+        Some((ip, port)) => Some(SocketAddrV4::new(ipv4(ip)?, port_number(port)?)),
+        None => None,
+    };
+
+    match link < crate::MAX_LINKS {
+        true => Some((link, len, source)),
+        false => None,
+    }
+}
+
+/// Parses a port number
+fn port_number(digits: &[u8]) -> Option<u16> {
+    decimal(digits)
+        .filter(|port| *port <= u16::MAX as usize)
+        .map(|port| port as u16)
+}
+
+/// Splits the parameters of a response at the commas that are not quoted
+pub fn params(line: &[u8]) -> Params<'_> {
+    Params { rest: Some(line) }
+}
+
+pub struct Params<'a> {
+    rest: Option<&'a [u8]>,
+}
+
+impl<'a> Iterator for Params<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let rest = self.rest?;
+        let mut quoted = false;
+        let mut escaped = false;
+
+        for (i, b) in rest.iter().enumerate() {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' if quoted => escaped = true,
+                b'"' => quoted = !quoted,
+                b',' if !quoted => {
+                    self.rest = Some(&rest[(i + 1)..]);
+                    return Some(&rest[0..i]);
+                }
+                _ => {}
+            }
+        }
+
+        self.rest = None;
+        Some(rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::consts;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(decimal(b"0"), Some(0));
+        assert_eq!(decimal(b"4096"), Some(4096));
+        assert_eq!(decimal(b""), None);
+        assert_eq!(decimal(b"12a"), None);
+        assert_eq!(decimal(b"99999999999999999999999"), None);
+        assert_eq!(signed(b"-61"), Some(-61));
+        assert_eq!(signed(b"7"), Some(7));
+        assert_eq!(signed(b"-"), None);
+        assert_eq!(hex(b"01090000"), Some(0x0109_0000));
+        assert_eq!(hex(b"fF"), Some(0xff));
+        assert_eq!(hex(b"123456789"), None);
+        assert_eq!(hex(b"0x1"), None);
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(ipv4(b"192.168.4.1"), Some(Ipv4Addr::new(192, 168, 4, 1)));
+        assert_eq!(ipv4(b"\"10.0.0.2\""), Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(ipv4(b"1.2.3"), None);
+        assert_eq!(ipv4(b"1.2.3.4.5"), None);
+        assert_eq!(ipv4(b"1.2.3.256"), None);
+        assert_eq!(
+            mac(b"\"aa:BB:cc:dd:ee:0f\""),
+            Some([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f])
+        );
+        assert_eq!(mac(b"aa:bb:cc:dd:ee"), None);
+        assert_eq!(mac(b"aa:bb:cc:dd:ee:ff:00"), None);
+        assert_eq!(mac(b"a:bb:cc:dd:ee:ff"), None);
+        assert_eq!(mac(b"aa:bb:cc:dd:ee:fg"), None);
+    }
+
+    #[test]
+    fn ipd_headers() {
+        assert_eq!(ipd_header(b"5"), Some((0, 5, None)));
+        assert_eq!(ipd_header(b"4,1460"), Some((4, 1460, None)));
+        let source = SocketAddrV4::new(Ipv4Addr::new(192, 168, 4, 2), 5000);
+        assert_eq!(
+            ipd_header(b"2,8,192.168.4.2,5000"),
+            Some((2, 8, Some(source)))
+        );
+        assert_eq!(
+            ipd_header(b"3,\"192.168.4.2\",5000"),
+            Some((0, 3, Some(source)))
+        );
+        assert_eq!(ipd_header(b"2,8,192.168.4.2,70000"), None);
+        assert_eq!(ipd_header(b"2,8,192.168.4,5000"), None);
+        assert_eq!(ipd_header(b"2,8,192.168.4.2,5000,1"), None);
+        assert_eq!(ipd_header(b"5,10"), None);
+        assert_eq!(ipd_header(b"1,2,3"), None);
+        assert_eq!(ipd_header(b""), None);
+        assert_eq!(ipd_header(b"1,x"), None);
+    }
+
+    #[test]
+    fn escaped_strings() {
+        let s: String<consts::U8> = unescape(b"\"a\\,b\\\"\\\\\"").unwrap();
+        assert_eq!(s.as_str(), "a,b\"\\");
+        assert!(unescape::<consts::U4>(b"\"abcde\"").is_none());
+        assert!(unescape::<consts::U4>(b"\"\xff\"").is_none());
+        assert!(unescape::<consts::U4>(b"abc").is_none());
+    }
+
+    #[test]
+    fn quoted_params() {
+        let mut p = params(b"3,\"a,\\\"b\",-61,\"\"");
+        assert_eq!(p.next(), Some(&b"3"[..]));
+        assert_eq!(p.next(), Some(&b"\"a,\\\"b\""[..]));
+        assert_eq!(p.next(), Some(&b"-61"[..]));
+        assert_eq!(p.next(), Some(&b"\"\""[..]));
+        assert_eq!(p.next(), None);
+
+        let mut p = params(b"");
+        assert_eq!(p.next(), Some(&b""[..]));
+        assert_eq!(p.next(), None);
+
+        assert_eq!(unquote(b"\"home\""), Some(&b"home"[..]));
+        assert_eq!(unquote(b"home"), None);
+    }
+}
@@ -0,0 +1,755 @@
+//! Tests of the driver against a scripted serial port
+
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+use embedded_hal_mock::eh1::delay::NoopDelay;
+use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction};
+
+use super::*;
+
+/// A serial port that answers the commands that are written to it
+struct Serial {
+    input: VecDeque<u8>,
+    output: Vec<u8>,
+    replies: VecDeque<(&'static [u8], &'static [u8])>,
+}
+
+impl Serial {
+    /// `input` is what the module sent before the first command
+    fn new(input: &[u8]) -> Self {
+        Serial {
+            input: input.iter().copied().collect(),
+            output: Vec::new(),
+            replies: VecDeque::new(),
+        }
+    }
+
+    /// Sends `reply` once the driver wrote `command`,
+    /// replies to the same command are sent in the order they were added
+    fn reply(mut self, command: &'static [u8], reply: &'static [u8]) -> Self {
+        self.replies.push_back((command, reply));
+        self
+    }
+}
+
+impl ErrorType for Serial {
+    type Error = core::convert::Infallible;
+}
+
+impl Read for Serial {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        // a few bytes at a time, so the input buffer is refilled in the middle of lines
+        let n = buf.len().min(self.input.len()).min(7);
+        for b in buf.iter_mut().take(n) {
+            *b = self.input.pop_front().unwrap();
+        }
+
+        Ok(n)
+    }
+}
+
+impl ReadReady for Serial {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.input.is_empty())
+    }
+}
+
+impl Write for Serial {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.output.extend_from_slice(buf);
+        let output = &self.output;
+        let reply = self
+            .replies
+            .iter()
+            .position(|(command, _)| output.ends_with(command));
+        if let Some((_, reply)) = reply.and_then(|i| self.replies.remove(i)) {
+            self.input.extend(reply.iter());
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// A timer that expires after it was checked a number of times
+struct Countdown(u32);
+
+impl CountDown for Countdown {
+    type Time = Milliseconds;
+
+    fn start<U: Into<Milliseconds>>(&mut self, _count: U) {
+        self.0 = 100;
+    }
+
+    fn wait(&mut self) -> nb::Result<(), void::Void> {
+        self.0 = self.0.saturating_sub(1);
+        match self.0 {
+            0 => Ok(()),
+            _ => Err(nb::Error::WouldBlock),
+        }
+    }
+}
+
+/// Returns a driver in station mode that is connected to an access point
+fn joined(serial: Serial) -> Esp01<Serial, StationMode<APConnected<LinkDisconnected>>> {
+    let serial = serial
+        .reply(b"AT+CWMODE_CUR=1\r\n", b"AT+CWMODE_CUR=1\r\r\n\r\nOK\r\n")
+        .reply(
+            b"AT+CWJAP_CUR=\"ap\",\"secret\"\r\n",
+            b"AT+CWJAP_CUR=\"ap\",\"secret\"\r\r\nWIFI CONNECTED\r\nWIFI GOT IP\r\n\r\nOK\r\n",
+        );
+
+    esp01(serial)
+        .set_mode(Station, Persist::DontSave)
+        .unwrap()
+        .connect_ap("ap", "secret", Persist::DontSave)
+        .unwrap()
+}
+
+/// Returns a driver connected to an access point in multi-connection mode
+fn joined_multiple(serial: Serial) -> Esp01<Serial, StationMode<APConnected<MultipleLinks>>> {
+    let serial = serial.reply(b"AT+CIPMUX=1\r\n", b"AT+CIPMUX=1\r\r\n\r\nOK\r\n");
+
+    joined(serial).enable_multiple_connections().unwrap()
+}
+
+/// Joins the parts of a command
+fn command(parts: &[&str]) -> std::string::String {
+    parts.concat()
+}
+
+#[test]
+fn access_points() {
+    let ap = AccessPoint::parse(
+        b"+CWLAP:(3,\"home, sweet\",-61,\"aa:bb:cc:dd:ee:ff\",6,-6,0)",
+        ScanFields::ALL,
+    )
+    .unwrap();
+    assert_eq!(ap.encryption, Some(Encryption::WPA2));
+    assert_eq!(
+        ap.ssid.as_ref().map(|ssid| ssid.as_str()),
+        Some("home, sweet")
+    );
+    assert_eq!(ap.rssi, Some(-61));
+    assert_eq!(ap.mac, Some([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]));
+    assert_eq!(ap.channel, Some(6));
+
+    let ap = AccessPoint::parse(
+        b"+CWLAP:(\"home\",-70)",
+        ScanFields::SSID | ScanFields::RSSI,
+    )
+    .unwrap();
+    assert_eq!(ap.encryption, None);
+    assert_eq!(ap.ssid.as_ref().map(|ssid| ssid.as_str()), Some("home"));
+    assert_eq!(ap.rssi, Some(-70));
+    assert_eq!(ap.channel, None);
+
+    let ap = AccessPoint::parse(
+        b"+CWLAP:(3,\"0123456789012345678901234567890\\,\",-61,\"aa:bb:cc:dd:ee:ff\",6)",
+        ScanFields::ALL,
+    )
+    .unwrap();
+    assert_eq!(
+        ap.ssid.as_ref().map(|ssid| ssid.as_str()),
+        Some("0123456789012345678901234567890,")
+    );
+    assert_eq!(ap.channel, Some(6));
+
+    let ap = AccessPoint::parse(
+        b"+CWLAP:(3,\"0123456789012345678901234567890123\",-61,\"aa:bb:cc:dd:ee:ff\",6)",
+        ScanFields::ALL,
+    )
+    .unwrap();
+    assert_eq!(ap.ssid, None);
+    assert_eq!(ap.rssi, Some(-61));
+
+    assert!(AccessPoint::parse(b"+CWLAP:(3,\"home\"", ScanFields::ALL).is_none());
+    assert!(AccessPoint::parse(b"+CWLIF:(3)", ScanFields::ALL).is_none());
+}
+
+#[test]
+fn link_status() {
+    let link = LinkStatus::parse(b"+CIPSTATUS:2,\"UDP\",\"192.168.4.2\",5000,80,1").unwrap();
+    assert_eq!(link.link, LinkId(2));
+    assert_eq!(link.connection_mode, ConnectionMode::UDP);
+    assert_eq!(link.remote_ip, Ipv4Addr::new(192, 168, 4, 2));
+    assert_eq!(link.remote_port, 5000);
+    assert_eq!(link.local_port, 80);
+    assert!(link.server);
+
+    assert!(LinkStatus::parse(b"+CIPSTATUS:5,\"TCP\",\"1.2.3.4\",80,4000,0").is_none());
+    assert!(LinkStatus::parse(b"+CIPSTATUS:0,\"TCP\",\"1.2.3.4\",80").is_none());
+    assert!(LinkStatus::parse(b"STATUS:3").is_none());
+}
+
+#[test]
+fn connect_commands() {
+    let options = ConnectOptions::new(ConnectionMode::TCP, "example.com", 80);
+    let tcp = ConnectCommand::new(None, &options);
+    assert_eq!(command(&tcp.parts()), "CIPSTART=\"TCP\",\"example.com\",80");
+    assert!(tcp.sni_parts().is_none());
+
+    let options =
+        ConnectOptions::new(ConnectionMode::TCP, Ipv4Addr::new(1, 2, 3, 4), 80).keepalive(60);
+    let tcp = ConnectCommand::new(Some(LinkId(1)), &options);
+    assert_eq!(
+        command(&tcp.parts()),
+        "CIPSTART=1,\"TCP\",\"1.2.3.4\",80,60"
+    );
+    assert_eq!(
+        command(&tcp.translink_parts()),
+        "SAVETRANSLINK=1,\"1.2.3.4\",80,\"TCP\",60"
+    );
+
+    let options = ConnectOptions::new(ConnectionMode::UDP, "1.2.3.4", 53)
+        .local_port(1234, UdpMode::ChangeAlways);
+    let udp = ConnectCommand::new(Some(LinkId(0)), &options);
+    assert_eq!(
+        command(&udp.parts()),
+        "CIPSTART=0,\"UDP\",\"1.2.3.4\",53,1234,2"
+    );
+    assert_eq!(
+        command(&udp.translink_parts()),
+        "SAVETRANSLINK=1,\"1.2.3.4\",53,\"UDP\",1234"
+    );
+
+    let options = ConnectOptions::new(ConnectionMode::SSL, "example.com", 443).sni("example.com");
+    let ssl = ConnectCommand::new(Some(LinkId(4)), &options);
+    assert_eq!(
+        command(&ssl.sni_parts().unwrap()),
+        "CIPSSLCSNI=4,\"example.com\""
+    );
+}
+
+#[test]
+fn join_failures() {
+    fn station(serial: Serial) -> Esp01<Serial, StationMode<APDisconnected>, 512, 256, Countdown> {
+        let serial = serial.reply(b"AT+CWMODE_CUR=1\r\n", b"AT+CWMODE_CUR=1\r\r\n\r\nOK\r\n");
+        esp01_with_timer(serial, Countdown(0))
+            .set_mode(Station, Persist::DontSave)
+            .unwrap()
+    }
+
+    // ESP-AT 1.x
+    let serial = Serial::new(b"").reply(
+        b"AT+CWJAP_CUR=\"ap\",\"wrong\"\r\n",
+        b"AT+CWJAP_CUR=\"ap\",\"wrong\"\r\r\n+CWJAP:2\r\n\r\nFAIL\r\n",
+    );
+    let join = station(serial).connect_ap("ap", "wrong", Persist::DontSave);
+    assert!(matches!(
+        join,
+        Err(Error::JoinFailed(JoinError::WrongPassword))
+    ));
+
+    // ESP-AT 2.x
+    let serial = Serial::new(b"").reply(
+        b"AT+CWJAP_CUR=\"ap\",\"secret\"\r\n",
+        b"AT+CWJAP_CUR=\"ap\",\"secret\"\r\r\n+CWJAP:3\r\n\r\nERROR\r\n",
+    );
+    let join = station(serial).connect_ap("ap", "secret", Persist::DontSave);
+    assert!(matches!(
+        join,
+        Err(Error::JoinFailed(JoinError::APNotFound))
+    ));
+
+    // without a reason the error is kept
+    let serial = Serial::new(b"").reply(
+        b"AT+CWJAP_CUR=\"ap\",\"secret\"\r\n",
+        b"AT+CWJAP_CUR=\"ap\",\"secret\"\r\r\n\r\nERROR\r\n",
+    );
+    let join = station(serial).connect_ap("ap", "secret", Persist::DontSave);
+    assert!(matches!(join, Err(Error::CommandError(None))));
+}
+
+#[test]
+fn join_options() {
+    fn join(options: &JoinOptions, command: &'static [u8], reply: &'static [u8]) -> bool {
+        let serial = Serial::new(b"")
+            .reply(b"AT+CWMODE_CUR=1\r\n", b"AT+CWMODE_CUR=1\r\r\n\r\nOK\r\n")
+            .reply(command, reply);
+        esp01_with_timer(serial, Countdown(0))
+            .set_mode(Station, Persist::DontSave)
+            .unwrap()
+            .connect_ap_with_options("ap", "secret", options, Persist::DontSave)
+            .is_ok()
+    }
+
+    // the parameters before the last one that is given are filled in
+    assert!(join(
+        &JoinOptions::new().reconnect_interval(5),
+        b"AT+CWJAP_CUR=\"ap\",\"secret\",,0,5\r\n",
+        b"WIFI CONNECTED\r\n\r\nOK\r\n",
+    ));
+    assert!(join(
+        &JoinOptions::new().listen_interval(10),
+        b"AT+CWJAP_CUR=\"ap\",\"secret\",,0,1,10\r\n",
+        b"\r\nOK\r\n",
+    ));
+    assert!(join(
+        &JoinOptions::new().bssid("aa:bb:cc:dd:ee:ff").pci_en(true),
+        b"AT+CWJAP_CUR=\"ap\",\"secret\",\"aa:bb:cc:dd:ee:ff\",1\r\n",
+        b"\r\nOK\r\n",
+    ));
+    assert!(join(
+        &JoinOptions::new().bssid("aa:bb:cc:dd:ee:ff"),
+        b"AT+CWJAP_CUR=\"ap\",\"secret\",\"aa:bb:cc:dd:ee:ff\"\r\n",
+        b"\r\nOK\r\n",
+    ));
+}
+
+#[test]
+fn response_overflow() {
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+GMR\r\n",
+            b"AT+GMR\r\r\nAT version:1.7.4.0(May 11 2020 19:13:04)\r\nSDK version:3.0.4\r\n\r\nOK\r\n",
+        )
+        .reply(b"AT+GMR\r\n", b"AT+GMR\r\r\nAT version:1.7\r\n\r\nOK\r\n");
+    let mut esp01: Esp01<_, _, 32, 8> = Esp01::new(serial);
+
+    assert_eq!(esp01.get_version(), Err(Error::ResponseOverflow));
+    // the rest of the long response was read, the next command gets its own response
+    assert_eq!(esp01.get_version(), Ok(&b"AT version:1.7"[..]));
+}
+
+#[test]
+fn ipd_within_response() {
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\n",
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\r\nCONNECT\r\n\r\nOK\r\n",
+        )
+        .reply(
+            b"AT+CIFSR\r\n",
+            b"AT+CIFSR\r\r\n+CIFSR:STAIP,\"192.168.1.5\"\r\n+IPD,4:OK\r\n\r\n+CIFSR:STAMAC,\"aa:bb:cc:dd:ee:ff\"\r\n\r\nOK\r\n",
+        );
+    let mut esp01 = joined(serial)
+        .connect(&ConnectOptions::new(ConnectionMode::TCP, "1.2.3.4", 80))
+        .unwrap();
+
+    // the `OK` in the data does not end the response
+    assert_eq!(esp01.get_local_ip(), Ok(Ipv4Addr::new(192, 168, 1, 5)));
+    let mut buf = [0; 8];
+    assert_eq!(esp01.receive(&mut buf), Ok(4));
+    assert_eq!(&buf[0..4], b"OK\r\n");
+}
+
+#[test]
+fn send_and_receive() {
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+CIPSTART=0,\"TCP\",\"1.2.3.4\",80\r\n",
+            b"AT+CIPSTART=0,\"TCP\",\"1.2.3.4\",80\r\r\n0,CONNECT\r\n\r\nOK\r\n",
+        )
+        .reply(
+            b"AT+CIPSTART=1,\"TCP\",\"1.2.3.4\",81\r\n",
+            b"AT+CIPSTART=1,\"TCP\",\"1.2.3.4\",81\r\r\n1,CONNECT\r\n\r\nOK\r\n",
+        )
+        // data and notifications arrive before the prompt and before the result
+        .reply(
+            b"AT+CIPSEND=0,5\r\n",
+            b"AT+CIPSEND=0,5\r\r\n\r\nOK\r\n\r\n+IPD,0,3:abc1,CLOSED\r\n> ",
+        )
+        .reply(
+            b"hello",
+            b"\r\nRecv 5 bytes\r\n+IPD,0,2:de\r\n\r\nSEND OK\r\n+IPD,1,2:fg",
+        );
+    let mut esp01 = joined_multiple(serial);
+    let first = esp01
+        .connect(&ConnectOptions::new(ConnectionMode::TCP, "1.2.3.4", 80))
+        .unwrap();
+    let second = esp01
+        .connect(&ConnectOptions::new(ConnectionMode::TCP, "1.2.3.4", 81))
+        .unwrap();
+
+    esp01.send(first, b"hello").unwrap();
+    assert!(esp01.is_connected(first));
+    assert!(!esp01.is_connected(second));
+
+    let mut buf = [0; 8];
+    assert_eq!(esp01.receive(first, &mut buf), Ok(5));
+    assert_eq!(&buf[0..5], b"abcde");
+    assert_eq!(esp01.receive(second, &mut buf), Ok(2));
+    assert_eq!(&buf[0..2], b"fg");
+    assert_eq!(esp01.receive(first, &mut buf), Ok(0));
+}
+
+#[test]
+fn notifications_while_sending() {
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\n",
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\r\nCONNECT\r\n\r\nOK\r\n",
+        )
+        .reply(b"AT+CIPSEND=2\r\n", b"AT+CIPSEND=2\r\r\n\r\nOK\r\n> ")
+        .reply(b"hi", b"\r\nRecv 2 bytes\r\nCLOSED\r\n\r\nSEND OK\r\n");
+    let mut esp01 = joined(serial)
+        .connect(&ConnectOptions::new(ConnectionMode::TCP, "1.2.3.4", 80))
+        .unwrap();
+
+    esp01.send(b"hi").unwrap();
+    assert!(!esp01.is_connected());
+    let mut urcs = core::iter::from_fn(|| esp01.poll_urc().unwrap());
+    assert!(urcs.any(|urc| urc == Urc::LinkClosed(LinkId(0))));
+
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\n",
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\r\nCONNECT\r\n\r\nOK\r\n",
+        )
+        .reply(b"AT+CIPSEND=2\r\n", b"AT+CIPSEND=2\r\r\n\r\nOK\r\n> ")
+        .reply(b"hi", b"\r\nbusy s...\r\n");
+    let mut esp01 = joined(serial)
+        .connect(&ConnectOptions::new(ConnectionMode::TCP, "1.2.3.4", 80))
+        .unwrap();
+
+    assert_eq!(esp01.send(b"hi"), Err(Error::Busy));
+}
+
+#[test]
+fn ssl_buffer_size() {
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+CIPSSLSIZE=4096\r\n",
+            b"AT+CIPSSLSIZE=4096\r\r\n\r\nOK\r\n",
+        )
+        .reply(
+            b"AT+CIPSTART=0,\"SSL\",\"1.2.3.4\",443\r\n",
+            b"AT+CIPSTART=0,\"SSL\",\"1.2.3.4\",443\r\r\n0,CONNECT\r\n\r\nOK\r\n\r\nready\r\n",
+        );
+    let mut esp01 = joined_multiple(serial);
+    let options = ConnectOptions::new(ConnectionMode::SSL, "1.2.3.4", 443);
+
+    assert_eq!(
+        esp01.set_ssl_buffer_size(1024),
+        Err(Error::InvalidSslBufferSize)
+    );
+    assert_eq!(esp01.connect(&options), Err(Error::SslNotConfigured));
+    esp01.set_ssl_buffer_size(4096).unwrap();
+    let link = esp01.connect(&options).unwrap();
+
+    // the module restarted and forgot the size
+    while esp01.poll_urc().unwrap().is_some() {}
+    assert!(!esp01.is_connected(link));
+    assert_eq!(esp01.connect(&options), Err(Error::SslNotConfigured));
+}
+
+#[test]
+fn receive_overflow() {
+    let serial = Serial::new(b"")
+        .reply(b"AT+CWMODE_CUR=1\r\n", b"AT+CWMODE_CUR=1\r\r\n\r\nOK\r\n")
+        .reply(
+            b"AT+CWJAP_CUR=\"ap\",\"secret\"\r\n",
+            b"AT+CWJAP_CUR=\"ap\",\"secret\"\r\r\n\r\nOK\r\n",
+        )
+        .reply(
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\n",
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\r\nCONNECT\r\n\r\nOK\r\n+IPD,6:abcdef\r\n",
+        );
+    let esp01: Esp01<_, _, 512, 4> = Esp01::new(serial);
+    let mut esp01 = esp01
+        .set_mode(Station, Persist::DontSave)
+        .unwrap()
+        .connect_ap("ap", "secret", Persist::DontSave)
+        .unwrap()
+        .connect(&ConnectOptions::new(ConnectionMode::TCP, "1.2.3.4", 80))
+        .unwrap();
+
+    let mut buf = [0; 8];
+    assert_eq!(esp01.receive(&mut buf), Err(Error::ReceiveOverflow));
+    assert_eq!(esp01.receive(&mut buf), Ok(4));
+    assert_eq!(&buf[0..4], b"abcd");
+}
+
+#[test]
+fn status() {
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+CIPSTATUS\r\n",
+            b"AT+CIPSTATUS\r\r\nSTATUS:3\r\n+CIPSTATUS:0,\"TCP\",\"1.2.3.4\",80,4000,0\r\n+CIPSTATUS:2,\"UDP\",\"1.2.3.4\",53,5000,0\r\n\r\nOK\r\n+IPD,2,2:ab\r\n+IPD,2,2:cd\r\n",
+        )
+        .reply(
+            b"AT+CIPCLOSE=0\r\n",
+            b"AT+CIPCLOSE=0\r\r\n0,CLOSED\r\n\r\nOK\r\n",
+        )
+        .reply(
+            b"AT+CIPSTATUS\r\n",
+            b"AT+CIPSTATUS\r\r\nSTATUS:4\r\n\r\nOK\r\n",
+        );
+    let mut esp01 = joined_multiple(serial);
+
+    // the driver takes over the links the module reports
+    let status = esp01.status().unwrap();
+    assert_eq!(status.status, ConnectionStatus::Connected);
+    assert_eq!(status.links[0].map(|link| link.remote_port), Some(80));
+    assert_eq!(
+        status.links[2].map(|link| link.connection_mode),
+        Some(ConnectionMode::UDP)
+    );
+    assert!(status.links[1].is_none());
+    assert!(esp01.is_connected(LinkId(0)));
+    assert!(!esp01.is_connected(LinkId(1)));
+    assert!(esp01.is_connected(LinkId(2)));
+
+    // the UDP link is received one datagram at a time
+    let mut buf = [0; 8];
+    assert_eq!(esp01.receive(LinkId(2), &mut buf), Ok(2));
+    assert_eq!(&buf[0..2], b"ab");
+
+    esp01.close(LinkId(0)).unwrap();
+    assert!(!esp01.is_connected(LinkId(0)));
+    let status = esp01.status().unwrap();
+    assert_eq!(status.status, ConnectionStatus::Disconnected);
+    assert!(!esp01.is_connected(LinkId(2)));
+}
+
+#[test]
+fn datagrams() {
+    let serial = Serial::new(b"").reply(
+        b"AT+CIPSTART=0,\"UDP\",\"1.2.3.4\",53\r\n",
+        b"AT+CIPSTART=0,\"UDP\",\"1.2.3.4\",53\r\r\n0,CONNECT\r\n\r\nOK\r\n+IPD,0,3:abc\r\n+IPD,0,5:defgh\r\n+IPD,0,2:ij\r\n",
+    );
+    let mut esp01 = joined_multiple(serial);
+    let link = esp01
+        .connect(&ConnectOptions::new(ConnectionMode::UDP, "1.2.3.4", 53))
+        .unwrap();
+
+    let mut buf = [0; 4];
+    assert_eq!(esp01.receive(link, &mut buf), Ok(3));
+    assert_eq!(&buf[0..3], b"abc");
+    // the rest of a datagram that does not fit is dropped
+    assert_eq!(esp01.receive(link, &mut buf), Ok(4));
+    assert_eq!(&buf, b"defg");
+    assert_eq!(esp01.receive(link, &mut buf), Ok(2));
+    assert_eq!(&buf[0..2], b"ij");
+    assert_eq!(esp01.receive(link, &mut buf), Ok(0));
+}
+
+#[test]
+fn datagram_sources() {
+    let serial = Serial::new(b"")
+        .reply(b"AT+CIPDINFO=1\r\n", b"AT+CIPDINFO=1\r\r\n\r\nOK\r\n")
+        .reply(
+            b"AT+CIPSTART=0,\"UDP\",\"1.2.3.4\",53\r\n",
+            b"AT+CIPSTART=0,\"UDP\",\"1.2.3.4\",53\r\r\n0,CONNECT\r\n\r\nOK\r\n+IPD,0,3,1.2.3.4,53:abc\r\n+IPD,0,2,\"5.6.7.8\",1234:de\r\n",
+        );
+    let mut esp01 = joined_multiple(serial);
+    esp01.set_ipd_info(true).unwrap();
+    let link = esp01
+        .connect(&ConnectOptions::new(ConnectionMode::UDP, "1.2.3.4", 53))
+        .unwrap();
+
+    let mut buf = [0; 4];
+    assert_eq!(
+        esp01.receive_from(link, &mut buf),
+        Ok((3, Some(SocketAddrV4::new(Ipv4Addr::new(1, 2, 3, 4), 53))))
+    );
+    assert_eq!(&buf[0..3], b"abc");
+    assert_eq!(
+        esp01.receive_from(link, &mut buf),
+        Ok((2, Some(SocketAddrV4::new(Ipv4Addr::new(5, 6, 7, 8), 1234))))
+    );
+    assert_eq!(&buf[0..2], b"de");
+    assert_eq!(esp01.receive_from(link, &mut buf), Ok((0, None)));
+}
+
+#[cfg(feature = "embedded-nal")]
+#[test]
+fn nal_datagram_sources() {
+    use embedded_nal::UdpClientStack;
+
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+CIPSTART=0,\"UDP\",\"1.2.3.4\",53\r\n",
+            b"AT+CIPSTART=0,\"UDP\",\"1.2.3.4\",53\r\r\n0,CONNECT\r\n\r\nOK\r\n+IPD,0,1:a\r\n+IPD,0,1,5.6.7.8,1234:b\r\n",
+        );
+    let mut stack = nal::Stack::new(joined_multiple(serial));
+    let remote = "1.2.3.4:53".parse().unwrap();
+    let mut socket = stack.socket().unwrap();
+    stack.connect(&mut socket, remote).unwrap();
+
+    // without the source the connected address is returned
+    let mut buf = [0; 4];
+    assert_eq!(stack.receive(&mut socket, &mut buf), Ok((1, remote)));
+    assert_eq!(
+        stack.receive(&mut socket, &mut buf),
+        Ok((1, "5.6.7.8:1234".parse().unwrap()))
+    );
+}
+
+#[test]
+fn accept() {
+    let serial = Serial::new(b"")
+        .reply(b"AT+CWMODE_CUR=2\r\n", b"AT+CWMODE_CUR=2\r\r\n\r\nOK\r\n")
+        .reply(b"AT+CIPMUX=1\r\n", b"AT+CIPMUX=1\r\r\n\r\nOK\r\n")
+        .reply(b"AT+CIPSERVER=1,80\r\n", b"AT+CIPSERVER=1,80\r\r\n\r\nOK\r\n")
+        .reply(
+            b"AT+CIPSTART=0,\"TCP\",\"192.168.4.2\",80\r\n",
+            b"AT+CIPSTART=0,\"TCP\",\"192.168.4.2\",80\r\r\n0,CONNECT\r\n\r\nOK\r\n1,CONNECT\r\n+IPD,1,2:hi",
+        );
+    let mut esp01 = esp01(serial)
+        .set_mode(SoftAP, Persist::DontSave)
+        .unwrap()
+        .enable_multiple_connections()
+        .unwrap();
+
+    esp01.start_server(80).unwrap();
+    assert_eq!(esp01.accept(), Err(nb::Error::WouldBlock));
+
+    // only the link a client opened is accepted, not the one the driver opened
+    let link = esp01
+        .connect(&ConnectOptions::new(ConnectionMode::TCP, "192.168.4.2", 80))
+        .unwrap();
+    assert_eq!(link, LinkId(0));
+    assert_eq!(esp01.accept(), Ok(LinkId(1)));
+    assert_eq!(esp01.accept(), Err(nb::Error::WouldBlock));
+
+    let mut buf = [0; 4];
+    assert_eq!(esp01.receive(LinkId(1), &mut buf), Ok(2));
+    assert_eq!(&buf[0..2], b"hi");
+}
+
+#[test]
+fn without_echo() {
+    let serial = Serial::new(b"")
+        .reply(b"AT+GMR\r\n", b"\r\nAT version:1.7\r\n\r\nOK\r\n")
+        .reply(b"AT+CWMODE_CUR?\r\n", b"+CWMODE_CUR:1\r\n\r\nOK\r\n")
+        .reply(b"AT+CWJAP_CUR?\r\n", b"No AP\r\n\r\nOK\r\n")
+        .reply(b"AT+CIPMUX?\r\n", b"+CIPMUX:0\r\n\r\nOK\r\n")
+        .reply(b"AT+CIPSTATUS\r\n", b"STATUS:5\r\n\r\nOK\r\n");
+    let mut esp01 = esp01(serial);
+
+    assert_eq!(esp01.get_version(), Ok(&b"AT version:1.7"[..]));
+    assert!(matches!(
+        esp01.probe(),
+        Ok(Probe::Station(StationProbe::Disconnected(_)))
+    ));
+}
+
+#[test]
+fn echo() {
+    let serial = Serial::new(b"")
+        .reply(b"ATE0\r\n", b"ATE0\r\r\n\r\nOK\r\n")
+        .reply(b"AT+GMR\r\n", b"AT version:1.7\r\n\r\nOK\r\n\r\nready\r\n")
+        .reply(b"ATE1\r\n", b"ATE1\r\r\n\r\nOK\r\n");
+    let mut esp01 = esp01(serial);
+    assert!(esp01.echo_enabled());
+
+    esp01.set_echo(false).unwrap();
+    assert!(!esp01.echo_enabled());
+    assert_eq!(esp01.get_version(), Ok(&b"AT version:1.7"[..]));
+
+    // the module restarted
+    assert_eq!(esp01.poll_urc(), Ok(Some(Urc::Ready)));
+    assert!(esp01.echo_enabled());
+    esp01.set_echo(true).unwrap();
+    assert!(esp01.echo_enabled());
+}
+
+#[test]
+fn configure_uart() {
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+UART_CUR=921600,8,1,0,3\r\n",
+            b"AT+UART_CUR=921600,8,1,0,3\r\r\n\r\nOK\r\n",
+        )
+        .reply(
+            b"AT+UART_DEF=9600,7,3,2,0\r\n",
+            b"AT+UART_DEF=9600,7,3,2,0\r\r\n\r\nOK\r\n",
+        );
+    let mut esp01 = esp01_with_timer(serial, Countdown(0));
+
+    let config = UartConfig::new(921_600).flow_control(FlowControl::RtsCts);
+    assert_eq!(esp01.configure_uart(config, Persist::DontSave), Ok(()));
+    let config = UartConfig::new(9600)
+        .data_bits(DataBits::Seven)
+        .stop_bits(StopBits::Two)
+        .parity(Parity::Even);
+    assert_eq!(esp01.configure_uart(config, Persist::SaveInFlash), Ok(()));
+    assert!(esp01.serial_mut().replies.is_empty());
+}
+
+#[test]
+fn timeout_while_streaming() {
+    let mut serial = Serial::new(b"AT+GMR\r\r\n");
+    serial.input.extend(core::iter::repeat_n(b'x', 10_000));
+    let mut esp01 = esp01_with_timer(serial, Countdown(0));
+
+    assert_eq!(esp01.get_version(), Err(Error::Timeout));
+}
+
+#[test]
+fn passthrough() {
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\n",
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\r\nCONNECT\r\n\r\nOK\r\n+IPD,3:old",
+        )
+        .reply(b"AT+CIPMODE=1\r\n", b"AT+CIPMODE=1\r\r\n\r\nOK\r\n")
+        .reply(b"AT+CIPSEND\r\n", b"AT+CIPSEND\r\r\n\r\nOK\r\n\r\n>")
+        .reply(b"hello", b"world")
+        .reply(b"+++", b"late")
+        .reply(b"AT+CIPMODE=0\r\n", b"AT+CIPMODE=0\r\r\n\r\nOK\r\n");
+    let mut esp01 = joined(serial)
+        .connect(&ConnectOptions::new(ConnectionMode::TCP, "1.2.3.4", 80))
+        .unwrap()
+        .start_passthrough()
+        .unwrap();
+
+    // the data is written and read as it is
+    esp01.write_all(b"hello").unwrap();
+    let mut buf = [0; 8];
+    assert_eq!(esp01.read(&mut buf), Ok(3));
+    assert_eq!(&buf[0..3], b"old");
+    let mut data = Vec::new();
+    while data.len() < 5 {
+        let n = esp01.read(&mut buf).unwrap();
+        data.extend_from_slice(&buf[0..n]);
+    }
+    assert_eq!(data, b"world");
+
+    // data that arrives before the module stops transmitting is kept
+    let mut esp01 = esp01.exit_passthrough(&mut NoopDelay::new()).unwrap();
+    assert_eq!(esp01.receive(&mut buf), Ok(4));
+    assert_eq!(&buf[0..4], b"late");
+    assert!(esp01.serial_mut().output.ends_with(b"+++AT+CIPMODE=0\r\n"));
+}
+
+#[test]
+fn hardware_reset() {
+    let serial = Serial::new(b"\x8a\xff ets Jan  8 2013,rst cause:2\r\n\x00garbage\r\nready\r\n");
+    let mut pin = PinMock::new(&[Transaction::set(State::Low), Transaction::set(State::High)]);
+
+    let reset = esp01(serial).hardware_reset(&mut pin, &mut NoopDelay::new());
+    assert!(reset.is_ok());
+    pin.done();
+}
+
+#[test]
+fn hardware_reset_in_passthrough() {
+    let serial = Serial::new(b"")
+        .reply(
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\n",
+            b"AT+CIPSTART=\"TCP\",\"1.2.3.4\",80\r\r\nCONNECT\r\n\r\nOK\r\n",
+        )
+        .reply(b"AT+CIPMODE=1\r\n", b"AT+CIPMODE=1\r\r\n\r\nOK\r\n")
+        // the boot loader output runs into `ready` without a line end
+        .reply(
+            b"AT+CIPSEND\r\n",
+            b"AT+CIPSEND\r\r\n\r\nOK\r\n\r\n>data\x8a\xff\x00 rst cause:2ready\r\n",
+        );
+    let esp01 = joined(serial)
+        .connect(&ConnectOptions::new(ConnectionMode::TCP, "1.2.3.4", 80))
+        .unwrap()
+        .start_passthrough()
+        .unwrap();
+    let mut pin = PinMock::new(&[Transaction::set(State::Low), Transaction::set(State::High)]);
+
+    let reset = esp01.hardware_reset(&mut pin, &mut NoopDelay::new());
+    assert!(reset.is_ok());
+    pin.done();
+}
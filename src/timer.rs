@@ -0,0 +1,144 @@
+use embedded_hal::timer::CountDown;
+use heapless::{consts, Vec};
+
+use void::Void;
+
+/// A duration in milliseconds
+/// Timers used by the driver have to accept it with `T::Time: From<Milliseconds>`,
+/// HAL timers can be wrapped in a type that converts it.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Milliseconds(pub u32);
+
+/// A timer that never expires, used when the driver is created without a timer
+pub struct NoTimer;
+
+impl CountDown for NoTimer {
+    type Time = Milliseconds;
+
+    fn start<T>(&mut self, _count: T)
+    where
+        T: Into<Milliseconds>,
+    {
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        Err(nb::Error::WouldBlock)
+    }
+}
+
+/// Timeout for commands that are not listed in `TIMEOUTS`
+pub const DEFAULT_TIMEOUT: Milliseconds = Milliseconds(1_000);
+
+/// Time the module takes to boot and print `ready` after a reset
+pub const BOOT_TIMEOUT: Milliseconds = Milliseconds(5_000);
+
+/// Timeouts for commands that take longer than usual, by the start of the command
+const TIMEOUTS: [(&str, Milliseconds); 8] = [
+    ("CWJAP", Milliseconds(15_000)),
+    ("CWLAP", Milliseconds(10_000)),
+    ("CIPSTART", Milliseconds(10_000)),
+    ("CIPDOMAIN", Milliseconds(10_000)),
+    ("CIPSEND", Milliseconds(5_000)),
+    ("CIPCLOSE", Milliseconds(5_000)),
+    ("CWQAP", Milliseconds(5_000)),
+    ("RESTORE", Milliseconds(5_000)),
+];
+
+/// The timeouts of a driver, see `Esp01::timeouts_mut`
+/// Starts with timeouts that suit the commands, they can be changed for slow networks.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Timeouts {
+    default: Milliseconds,
+    boot: Milliseconds,
+    commands: Vec<(&'static str, Milliseconds), consts::U16>,
+}
+
+impl Timeouts {
+    pub fn new() -> Self {
+        Timeouts {
+            default: DEFAULT_TIMEOUT,
+            boot: BOOT_TIMEOUT,
+            commands: TIMEOUTS.iter().copied().collect(),
+        }
+    }
+
+    /// Sets the timeout for commands that start with `command`, like `"CWJAP"`
+    /// Returns `false` if there is no room left for another command.
+    pub fn set(&mut self, command: &'static str, timeout: Milliseconds) -> bool {
+        match self
+            .commands
+            .iter_mut()
+            .find(|(prefix, _)| *prefix == command)
+        {
+            Some(entry) => {
+                entry.1 = timeout;
+                true
+            }
+            None => self.commands.push((command, timeout)).is_ok(),
+        }
+    }
+
+    /// Sets the timeout for the commands that have no timeout of their own
+    pub fn set_default_timeout(&mut self, timeout: Milliseconds) {
+        self.default = timeout;
+    }
+
+    /// Sets how long the module may take to boot after a reset
+    pub fn set_boot_timeout(&mut self, timeout: Milliseconds) {
+        self.boot = timeout;
+    }
+
+    /// Returns the timeout that suits the command
+    pub fn command(&self, command: &[&str]) -> Milliseconds {
+        let name = command.first().copied().unwrap_or("");
+
+        self.commands
+            .iter()
+            .find(|(prefix, _)| name.starts_with(prefix))
+            .map(|(_, timeout)| *timeout)
+            .unwrap_or(self.default)
+    }
+
+    /// Returns the timeout for commands that have no timeout of their own
+    pub fn default_timeout(&self) -> Milliseconds {
+        self.default
+    }
+
+    /// Returns how long the module may take to boot after a reset
+    pub fn boot_timeout(&self) -> Milliseconds {
+        self.boot
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeouts() {
+        let mut timeouts = Timeouts::new();
+        assert_eq!(timeouts.command(&["CWJAP", "_CUR"]), Milliseconds(15_000));
+        assert_eq!(timeouts.command(&["GMR"]), DEFAULT_TIMEOUT);
+
+        timeouts.set_default_timeout(Milliseconds(200));
+        assert!(timeouts.set("CWJAP", Milliseconds(30_000)));
+        assert!(timeouts.set("CIPSTATUS", Milliseconds(2_000)));
+        assert_eq!(timeouts.command(&["CWJAP_DEF"]), Milliseconds(30_000));
+        assert_eq!(timeouts.command(&["CIPSTATUS"]), Milliseconds(2_000));
+        assert_eq!(timeouts.command(&["GMR"]), Milliseconds(200));
+
+        // the table is full
+        for command in ["A", "B", "C", "D", "E", "F", "G"].iter() {
+            assert!(timeouts.set(command, Milliseconds(1)));
+        }
+        assert!(!timeouts.set("H", Milliseconds(1)));
+        assert!(timeouts.set("CWLAP", Milliseconds(1)));
+    }
+}
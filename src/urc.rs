@@ -0,0 +1,126 @@
+use core::net::Ipv4Addr;
+
+use crate::parse;
+use crate::LinkId;
+
+/// A notification the module sends without being asked (unsolicited result code)
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Urc {
+    /// The module finished booting (`ready`)
+    Ready,
+    /// The module is still busy with the previous command (`busy p...`, `busy s...`)
+    Busy,
+    /// Connected to the access point (`WIFI CONNECTED`)
+    WifiConnected,
+    /// Got an IP address from the access point (`WIFI GOT IP`)
+    WifiGotIp,
+    /// Disconnected from the access point (`WIFI DISCONNECT`)
+    WifiDisconnected,
+    /// A link was opened (`<id>,CONNECT`), link 0 in single connection mode (`CONNECT`)
+    LinkConnected(LinkId),
+    /// A link was closed (`<id>,CLOSED`), link 0 in single connection mode (`CLOSED`)
+    LinkClosed(LinkId),
+    /// A station connected to the SoftAP (`+STA_CONNECTED:"<mac>"`)
+    StationConnected([u8; 6]),
+    /// A station disconnected from the SoftAP (`+STA_DISCONNECTED:"<mac>"`)
+    StationDisconnected([u8; 6]),
+    /// The SoftAP assigned an IP address to a station (`+DIST_STA_IP:"<mac>","<ip>"`)
+    StationIpAssigned { mac: [u8; 6], ip: Ipv4Addr },
+}
+
+impl Urc {
+    /// Parses a line, returns `None` if it is not a notification
+    pub(crate) fn parse(line: &[u8]) -> Option<Urc> {
+        let urc = match line {
+            b"ready" => Urc::Ready,
+            b"WIFI CONNECTED" => Urc::WifiConnected,
+            b"WIFI GOT IP" => Urc::WifiGotIp,
+            b"WIFI DISCONNECT" => Urc::WifiDisconnected,
+            b"CONNECT" => Urc::LinkConnected(LinkId(0)),
+            b"CLOSED" | b"CONNECT FAIL" => Urc::LinkClosed(LinkId(0)),
+            [id @ b'0'..=b'4', b',', status @ ..] => {
+                let link = LinkId(*id - b'0');
+                match status {
+                    b"CONNECT" => Urc::LinkConnected(link),
+                    b"CLOSED" | b"CONNECT FAIL" => Urc::LinkClosed(link),
+                    _ => return None,
+                }
+            }
+            _ if line.starts_with(b"busy ") => Urc::Busy,
+            _ => {
+                if let Some(mac) = line.strip_prefix(b"+STA_CONNECTED:") {
+                    Urc::StationConnected(parse::mac(mac)?)
+                } else if let Some(mac) = line.strip_prefix(b"+STA_DISCONNECTED:") {
+                    Urc::StationDisconnected(parse::mac(mac)?)
+                } else if let Some(params) = line.strip_prefix(b"+DIST_STA_IP:") {
+                    let mut params = parse::params(params);
+                    Urc::StationIpAssigned {
+                        mac: params.next().and_then(parse::mac)?,
+                        ip: params.next().and_then(parse::ipv4)?,
+                    }
+                } else {
+                    return None;
+                }
+            }
+        };
+
+        Some(urc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links() {
+        assert_eq!(Urc::parse(b"CONNECT"), Some(Urc::LinkConnected(LinkId(0))));
+        assert_eq!(Urc::parse(b"CLOSED"), Some(Urc::LinkClosed(LinkId(0))));
+        assert_eq!(
+            Urc::parse(b"3,CONNECT"),
+            Some(Urc::LinkConnected(LinkId(3)))
+        );
+        assert_eq!(Urc::parse(b"4,CLOSED"), Some(Urc::LinkClosed(LinkId(4))));
+        assert_eq!(
+            Urc::parse(b"2,CONNECT FAIL"),
+            Some(Urc::LinkClosed(LinkId(2)))
+        );
+        assert_eq!(Urc::parse(b"5,CONNECT"), None);
+        assert_eq!(Urc::parse(b"1,SEND OK"), None);
+    }
+
+    #[test]
+    fn wifi() {
+        assert_eq!(Urc::parse(b"ready"), Some(Urc::Ready));
+        assert_eq!(Urc::parse(b"busy p..."), Some(Urc::Busy));
+        assert_eq!(Urc::parse(b"busy s..."), Some(Urc::Busy));
+        assert_eq!(Urc::parse(b"WIFI CONNECTED"), Some(Urc::WifiConnected));
+        assert_eq!(Urc::parse(b"WIFI GOT IP"), Some(Urc::WifiGotIp));
+        assert_eq!(Urc::parse(b"WIFI DISCONNECT"), Some(Urc::WifiDisconnected));
+        assert_eq!(Urc::parse(b"OK"), None);
+        assert_eq!(Urc::parse(b""), None);
+    }
+
+    #[test]
+    fn stations() {
+        let mac = [0x18, 0xfe, 0x34, 0xa6, 0x0b, 0x1c];
+        assert_eq!(
+            Urc::parse(b"+STA_CONNECTED:\"18:fe:34:a6:0b:1c\""),
+            Some(Urc::StationConnected(mac))
+        );
+        assert_eq!(
+            Urc::parse(b"+STA_DISCONNECTED:\"18:fe:34:a6:0b:1c\""),
+            Some(Urc::StationDisconnected(mac))
+        );
+        assert_eq!(
+            Urc::parse(b"+DIST_STA_IP:\"18:fe:34:a6:0b:1c\",\"192.168.4.2\""),
+            Some(Urc::StationIpAssigned {
+                mac,
+                ip: Ipv4Addr::new(192, 168, 4, 2)
+            })
+        );
+        assert_eq!(Urc::parse(b"+STA_CONNECTED:\"18:fe\""), None);
+        assert_eq!(Urc::parse(b"+DIST_STA_IP:\"18:fe:34:a6:0b:1c\""), None);
+    }
+}